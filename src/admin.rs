@@ -0,0 +1,794 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::{fill, Config, Messages};
+use crate::conversation::{ButtonPress, Conversation, InlineButton, Keyboard, Reply, TextMessage};
+use crate::db::{QuestionStatus, SavedQuestion};
+use crate::diff::TreeDiff;
+use crate::report::Report;
+use crate::serde::QASerde;
+use crate::snapshot;
+use crate::util::logging::non_fatal;
+
+/// Команды администраторов и их описания для меню
+pub fn commands(messages: &Messages) -> [(&'static str, &str); 7] {
+    [
+        ("reload", &messages.command_reload),
+        ("stats", &messages.command_stats),
+        ("pending", &messages.command_pending),
+        ("answer", &messages.command_answer),
+        ("broadcast", &messages.command_broadcast),
+        ("promote", &messages.command_promote),
+        ("feedback", &messages.command_feedback),
+    ]
+}
+
+/// Сколько вопросов на модерации показывает одна страница /pending
+const PAGE_SIZE: usize = 5;
+
+/// Telegram не принимает сообщения длиннее 4096 символов
+const MESSAGE_LIMIT: usize = 4000;
+
+/// За сколько последних дней /stats показывает отчёт без аргументов и самое большее
+const DEFAULT_STATS_DAYS: u32 = 7;
+const MAX_STATS_DAYS: u32 = 366;
+
+/// Кнопки модерации присылают `mod:<действие>:<номер вопроса>`
+const MODERATION_PREFIX: &str = "mod:";
+
+/// Чего бот ждёт от администратора следующим сообщением
+#[derive(Debug)]
+enum Awaiting {
+    /// Ответа на вопрос после кнопки "Ответить"
+    Answer(i64),
+    /// Выбора категории для вопроса: путь от корня до текущей
+    Category { question_id: i64, path: Vec<String> },
+    /// Ответа, с которым вопрос попадёт в выбранную категорию
+    PromotedAnswer { question_id: i64, path: Vec<String> },
+    /// Выбора вопроса базы знаний, повтором которого оказался сохранённый
+    Original { question_id: i64, path: Vec<String> },
+}
+
+/// Команды, доступные только администраторам
+#[derive(Debug, Default)]
+pub struct Admin {
+    /// Telegram id администраторов. Личный чат с пользователем имеет тот же id
+    admins: Vec<u64>,
+    /// Чат, куда пересылаются новые сохранённые вопросы
+    admin_chat: Option<i64>,
+    knowledge_file: String,
+    snapshot_file: String,
+    /// От кого (чат и пользователь) ждём ответ или выбор категории, и с какого момента
+    awaiting: Mutex<HashMap<(i64, u64), (Instant, Awaiting)>>,
+    /// Сколько ждём, прежде чем забыть об ожидании
+    timeout: Duration,
+    /// Не даёт двум администраторам одновременно переписать файл базы знаний
+    editing: Mutex<()>,
+}
+
+impl Admin {
+    pub fn new(config: &Config) -> Self {
+        Admin {
+            admins: config.admins.clone(),
+            admin_chat: config.admin_chat,
+            knowledge_file: config.paths.knowledge_base.clone(),
+            snapshot_file: config.paths.snapshot.clone(),
+            awaiting: Mutex::new(HashMap::new()),
+            timeout: Duration::from_secs(config.admin_timeout.into()),
+            editing: Mutex::new(()),
+        }
+    }
+
+    pub fn admins(&self) -> &[u64] {
+        &self.admins
+    }
+
+    pub fn is_admin(&self, user_id: u64) -> bool {
+        self.admins.contains(&user_id)
+    }
+
+    /// Запоминает, чего ждём от пользователя в чате следующим сообщением
+    fn wait(&self, chat_id: i64, user_id: u64, awaiting: Awaiting) {
+        self.awaiting.lock().unwrap().insert((chat_id, user_id), (Instant::now(), awaiting));
+    }
+
+    /// Администратор или участник чата администраторов
+    fn is_moderator(&self, user_id: u64, chat_id: i64) -> bool {
+        self.is_admin(user_id) || self.admin_chat == Some(chat_id)
+    }
+
+    /// Выполняет команду администратора или принимает ответ на вопрос
+    /// после кнопки "Ответить" или ответом на пересланный вопрос;
+    /// `None`, если сообщение не для администрирования
+    pub fn handle(&self, conversation: &Conversation, message: &TextMessage) -> Option<Vec<Reply>> {
+        let chat_id = message.chat_id;
+        let user_id = message.user_id;
+        let in_admin_chat = self.admin_chat == Some(chat_id);
+        if !message.is_command {
+            if !self.is_moderator(user_id, chat_id) {
+                return None;
+            }
+            if let Some(id) = self.replied_question(conversation, message) {
+                return Some(Self::answer(conversation, chat_id, id, &message.text));
+            }
+            let Some((since, awaiting)) = self.awaiting.lock().unwrap().remove(&(chat_id, user_id)) else {
+                // Переписка в чате администраторов боту не адресована
+                return in_admin_chat.then(Vec::new);
+            };
+            // Забытое ожидание не должно превратить случайное сообщение в ответ
+            if since.elapsed() > self.timeout {
+                let expired = conversation.messages().admin_expired.clone();
+                return Some(vec![cancelled(&conversation.knowledge().load(), chat_id, expired)]);
+            }
+            return Some(match awaiting {
+                Awaiting::Answer(id) => Self::answer(conversation, chat_id, id, &message.text),
+                Awaiting::Category { question_id, path }
+                    => self.choose_category(conversation, chat_id, user_id, question_id, path, &message.text),
+                Awaiting::PromotedAnswer { question_id, path } => self.promote(conversation, chat_id, question_id, &path, &message.text),
+                Awaiting::Original { question_id, path }
+                    => self.choose_original(conversation, chat_id, user_id, question_id, path, &message.text),
+            });
+        }
+        // Любая команда отменяет ожидание ответа
+        self.awaiting.lock().unwrap().remove(&(chat_id, user_id));
+
+        let (command, args) = message.text
+                                     .split_once(char::is_whitespace)
+                                     .map_or((message.text.as_str(), ""), |(command, args)| (command, args.trim()));
+        let name = command.strip_prefix('/')?;
+        let messages = conversation.messages();
+        if !commands(messages).iter().any(|&(known, _)| known == name) {
+            return None;
+        }
+
+        // Участникам чата администраторов доступна только модерация кнопками и ответами
+        if !self.is_admin(user_id) {
+            return Some(vec![text(chat_id, messages.admin_only.clone())]);
+        }
+
+        let replies = match name {
+            "reload" => self.reload(conversation, chat_id),
+            "stats" => Self::stats(conversation, chat_id, args),
+            "pending" => Self::pending(conversation, chat_id, args),
+            "answer" => Self::answer_command(conversation, chat_id, args),
+            "broadcast" => Self::broadcast(conversation, chat_id, args),
+            "feedback" => vec![text(chat_id, Self::feedback(conversation))],
+            "promote" => match args.trim_start_matches('#').parse::<i64>() {
+                Ok(id) => self.start_promotion(conversation, chat_id, user_id, id),
+                Err(_) => vec![text(chat_id, messages.usage_promote.clone())],
+            },
+            _ => vec![],
+        };
+
+        Some(replies)
+    }
+
+    /// Обрабатывает кнопки модерации; `None`, если это другая кнопка
+    pub fn handle_button(&self, conversation: &Conversation, press: &ButtonPress) -> Option<Vec<Reply>> {
+        let (action, id) = press.data.strip_prefix(MODERATION_PREFIX)?.split_once(':')?;
+        let notify = |text: String| Reply::Notify { press_id: press.id.clone(), text };
+        let messages = conversation.messages();
+
+        let allowed = match action {
+            "reject" | "answer" | "duplicate" | "similar" | "cancel" => self.is_moderator(press.user_id, press.chat_id),
+            _ => self.is_admin(press.user_id),
+        };
+        if !allowed {
+            return Some(vec![notify(messages.admin_only.clone())]);
+        }
+        let Ok(id) = id.parse::<i64>() else {
+            return Some(vec![notify(messages.admin_bad_button.clone())]);
+        };
+        let db = conversation.question_db();
+        let remove_buttons = press.message_id.map(|message_id| Reply::RemoveButtons { chat_id: press.chat_id, message_id });
+        let not_found = || notify(fill(&messages.admin_not_found, &[("id", &id)]));
+        let error = |error: rusqlite::Error| notify(fill(&messages.admin_error, &[("error", &error)]));
+
+        let replies = match action {
+            "approve" => match db.set_status(id, QuestionStatus::Approved) {
+                Ok(true) => vec![notify(fill(&messages.admin_approved, &[("id", &id)]))],
+                Ok(false) => vec![not_found()],
+                Err(db_error) => vec![error(db_error)],
+            },
+            "reject" => match db.set_status(id, QuestionStatus::Rejected) {
+                Ok(true) => [notify(fill(&messages.admin_rejected, &[("id", &id)]))].into_iter().chain(remove_buttons).collect(),
+                Ok(false) => vec![not_found()],
+                Err(db_error) => vec![error(db_error)],
+            },
+            "answer" => match db.question(id) {
+                Ok(Some(question)) => {
+                    self.wait(press.chat_id, press.user_id, Awaiting::Answer(id));
+                    let cancel = InlineButton { text: messages.button_cancel.clone(), data: format!("{MODERATION_PREFIX}cancel:{id}") };
+                    vec![
+                        notify(String::new()),
+                        Reply::Message {
+                            chat_id: press.chat_id,
+                            text: fill(&messages.admin_answer_prompt, &[("id", &id), ("question", &question.question)]),
+                            keyboard: Some(Keyboard::Inline(vec![cancel])),
+                        },
+                    ]
+                }
+                Ok(None) => vec![not_found()],
+                Err(db_error) => vec![error(db_error)],
+            },
+            "cancel" => {
+                let key = (press.chat_id, press.user_id);
+                let mut awaiting = self.awaiting.lock().unwrap();
+                if matches!(awaiting.get(&key), Some((_, Awaiting::Answer(awaited))) if *awaited == id) {
+                    awaiting.remove(&key);
+                }
+                [notify(fill(&messages.admin_answer_cancelled, &[("id", &id)]))].into_iter().chain(remove_buttons).collect()
+            }
+            "promote" => [notify(String::new())].into_iter().chain(self.start_promotion(conversation, press.chat_id, press.user_id, id)).collect(),
+            "similar" => match db.question(id) {
+                Ok(Some(question)) => match similar_known(conversation, &question) {
+                    Some((original, answer)) => match Self::send_answer(conversation, press.chat_id, id, QuestionStatus::Duplicate, &answer) {
+                        Ok(mut replies) => {
+                            replies.insert(0, notify(String::new()));
+                            replies.extend(remove_buttons);
+                            replies.push(text(press.chat_id, fill(&messages.admin_duplicate, &[("id", &id), ("original", &original)])));
+                            replies
+                        }
+                        Err(replies) => [notify(String::new())].into_iter().chain(replies).collect(),
+                    },
+                    None => vec![notify(messages.admin_no_similar.clone())],
+                },
+                Ok(None) => vec![not_found()],
+                Err(db_error) => vec![error(db_error)],
+            },
+            "duplicate" => [notify(String::new())].into_iter().chain(self.start_duplicate(conversation, press.chat_id, press.user_id, id)).collect(),
+            _ => vec![notify(messages.admin_bad_button.clone())],
+        };
+
+        Some(replies)
+    }
+
+    /// Пересылает только что сохранённый вопрос в чат администраторов, если он задан
+    pub fn forward_question(&self, conversation: &Conversation, id: i64) -> Option<Reply> {
+        let chat_id = self.admin_chat?;
+        let question = conversation.question_db().question(id).map_err(non_fatal).ok()??;
+        // Повторы и вопросы, на которые бот ответил сам, модерировать не нужно
+        if question.status != QuestionStatus::New {
+            return None;
+        }
+
+        let messages = conversation.messages();
+        let (text, keyboard) = moderation_message(conversation, &question);
+        Some(Reply::Forward {
+            question_id: id,
+            chat_id,
+            text: truncate(fill(&messages.admin_new_question, &[("question", &text)])),
+            keyboard: Some(keyboard),
+        })
+    }
+
+    /// Вопрос, пересланный сообщением, на которое ответил администратор
+    fn replied_question(&self, conversation: &Conversation, message: &TextMessage) -> Option<i64> {
+        let replied = message.reply_to?;
+        conversation.question_db()
+                    .forwarded_question(message.chat_id, replied)
+                    .map_err(non_fatal)
+                    .ok()?
+    }
+
+    /// Перечитывает базу знаний и рассылает администраторам, что изменилось
+    fn reload(&self, conversation: &Conversation, chat_id: i64) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let qna = match snapshot::build(&self.knowledge_file, &self.snapshot_file) {
+            Ok(qna) => qna,
+            Err(error) => return vec![text(chat_id, fill(&messages.admin_reload_failed, &[("error", &error)]))],
+        };
+        let problems = qna.validate();
+        let questions = qna.question_id.len();
+
+        let old = conversation.knowledge().swap(qna);
+        let diff = TreeDiff::compute(&old, &conversation.knowledge().load());
+
+        let mut report = fill(&messages.admin_reloaded, &[("count", &questions)]);
+        match diff.is_empty() {
+            true => report.push_str(&format!("\n{}", messages.admin_no_changes)),
+            false => report.push_str(&format!("\n{}", fill(&messages.admin_changes, &[("count", &diff.changes.len())]))),
+        }
+        for change in &diff.changes {
+            report.push_str(&format!("\n{change}"));
+        }
+        if !problems.is_empty() {
+            report.push_str(&format!("\n{}", messages.admin_problems));
+            for problem in problems {
+                report.push_str(&format!("\n- {problem}"));
+            }
+        }
+        let report = truncate(report);
+
+        // Об изменениях узнают все администраторы, а не только тот, кто перезагрузил
+        let mut chats: Vec<i64> = self.admins.iter().map(|&admin| admin as i64).collect();
+        if !chats.contains(&chat_id) {
+            chats.insert(0, chat_id);
+        }
+        chats.into_iter().map(|chat| text(chat, report.clone())).collect()
+    }
+
+    /// Сводка и отчёт за последние дни (по умолчанию за неделю), с `csv` - ещё и файлом
+    fn stats(conversation: &Conversation, chat_id: i64, args: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let mut days = DEFAULT_STATS_DAYS;
+        let mut csv = false;
+        for arg in args.split_whitespace() {
+            match (arg, arg.parse::<u32>()) {
+                ("csv", _) => csv = true,
+                (_, Ok(number)) if (1..=MAX_STATS_DAYS).contains(&number) => days = number,
+                _ => return vec![text(chat_id, fill(&messages.usage_stats, &[("max", &MAX_STATS_DAYS)]))],
+            }
+        }
+
+        let db = conversation.question_db();
+        let saved = db.count_questions().map_or_else(|error| error.to_string(), |count| count.to_string());
+        let chats = db.chats().map_or_else(|error| error.to_string(), |chats| chats.len().to_string());
+        let questions = conversation.knowledge().load().question_id.len();
+        let summary = fill(&messages.admin_stats, &[("questions", &questions), ("saved", &saved), ("chats", &chats)]);
+
+        let report = match Report::last_days(db, days) {
+            Ok(report) => report,
+            Err(error) => return vec![text(chat_id, format!("{summary}\n\n{}", fill(&messages.admin_report_failed, &[("error", &error)])))],
+        };
+        let mut replies = vec![text(chat_id, truncate(format!("{summary}\n\n{}", report.render(messages))))];
+        if csv {
+            let mut content = vec![];
+            match report.write_csv(&mut content) {
+                Ok(()) => replies.push(Reply::Document { chat_id, file_name: report.csv_name(), content }),
+                Err(error) => replies.push(text(chat_id, fill(&messages.admin_export_failed, &[("error", &error)]))),
+            }
+        }
+
+        replies
+    }
+
+    /// Оценки ответов, начиная с самых неудачных
+    fn feedback(conversation: &Conversation) -> String {
+        let messages = conversation.messages();
+        let scores = match conversation.question_db().satisfaction() {
+            Ok(scores) if scores.is_empty() => return messages.admin_no_votes.clone(),
+            Ok(scores) => scores,
+            Err(error) => return fill(&messages.admin_error, &[("error", &error)]),
+        };
+
+        let mut report = messages.admin_votes.clone();
+        for score in scores {
+            let percent = format!("{:.0}", score.score() * 100.0);
+            report.push('\n');
+            report.push_str(&fill(&messages.admin_vote, &[
+                ("score", &percent),
+                ("helpful", &score.helpful),
+                ("unhelpful", &score.unhelpful),
+                ("question", &score.question),
+            ]));
+        }
+        truncate(report)
+    }
+
+    /// Страница открытых вопросов: заголовок и по сообщению с кнопками на вопрос
+    fn pending(conversation: &Conversation, chat_id: i64, args: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let page = match args {
+            "" => 1,
+            page => match page.parse::<usize>() {
+                Ok(page) if page > 0 => page,
+                _ => return vec![text(chat_id, messages.usage_pending.clone())],
+            },
+        };
+
+        let db = conversation.question_db();
+        let page_result = db.count_with_status(QuestionStatus::OPEN)
+                            .and_then(|total| Ok((total, db.questions_page(QuestionStatus::OPEN, (page - 1) * PAGE_SIZE, PAGE_SIZE)?)));
+        let (total, questions) = match page_result {
+            Ok(page) => page,
+            Err(error) => return vec![text(chat_id, fill(&messages.admin_error, &[("error", &error)]))],
+        };
+        if total == 0 {
+            return vec![text(chat_id, messages.admin_no_pending.clone())];
+        }
+
+        let pages = total.div_ceil(PAGE_SIZE);
+        let mut header = fill(&messages.admin_pending, &[("total", &total), ("page", &page), ("pages", &pages)]);
+        if page < pages {
+            header.push('\n');
+            header.push_str(&fill(&messages.admin_next_page, &[("page", &(page + 1))]));
+        }
+
+        let mut replies = vec![text(chat_id, header)];
+        // Как и новые вопросы, на них можно ответить ответом на сообщение
+        for question in questions {
+            let (text, keyboard) = moderation_message(conversation, &question);
+            replies.push(Reply::Forward { question_id: question.id, chat_id, text: truncate(text), keyboard: Some(keyboard) });
+        }
+        replies
+    }
+
+    fn answer_command(conversation: &Conversation, chat_id: i64, args: &str) -> Vec<Reply> {
+        let usage = || vec![text(chat_id, conversation.messages().usage_answer.clone())];
+        let Some((id, answer)) = args.split_once(char::is_whitespace) else {
+            return usage();
+        };
+        let Ok(id) = id.trim_start_matches('#').parse::<i64>() else {
+            return usage();
+        };
+
+        Self::answer(conversation, chat_id, id, answer.trim())
+    }
+
+    /// Записывает ответ на вопрос и отправляет его автору вопроса
+    fn answer(conversation: &Conversation, chat_id: i64, id: i64, answer: &str) -> Vec<Reply> {
+        let mut replies = match Self::send_answer(conversation, chat_id, id, QuestionStatus::Answered, answer) {
+            Ok(replies) => replies,
+            Err(replies) => return replies,
+        };
+        let messages = conversation.messages();
+        let confirmation = match replies.len() - 1 {
+            0 => fill(&messages.admin_answer_saved, &[("id", &id)]),
+            others => fill(&messages.admin_answer_saved_repeats, &[("id", &id), ("others", &others)]),
+        };
+        replies.push(text(chat_id, confirmation));
+        replies
+    }
+
+    /// Записывает ответ с новым статусом вопроса и готовит его для автора,
+    /// а также для авторов ещё не отвеченных повторов этого вопроса
+    fn send_answer(conversation: &Conversation, chat_id: i64, id: i64, status: QuestionStatus, answer: &str) -> Result<Vec<Reply>, Vec<Reply>> {
+        let db = conversation.question_db();
+        let question = saved_question(conversation, chat_id, id)?;
+        let repeats = db.cluster(id).map_err(non_fatal).unwrap_or_default();
+
+        let mut replies = vec![];
+        let unanswered = repeats.into_iter().filter(|repeat| repeat.answer.is_none());
+        for (question, status) in [(question, status)].into_iter().chain(unanswered.map(|repeat| (repeat, QuestionStatus::Duplicate))) {
+            if let Err(error) = db.answer_question(question.id, status, answer) {
+                let failed = fill(&conversation.messages().admin_answer_failed, &[("id", &question.id), ("error", &error)]);
+                return Err(vec![text(chat_id, failed)]);
+            }
+            let reply = conversation.messages()
+                                    .admin_answer
+                                    .replace("{question}", &question.question)
+                                    .replace("{answer}", answer);
+            replies.push(Reply::Answer { question_id: question.id, chat_id: question.chat_id, text: reply });
+        }
+
+        Ok(replies)
+    }
+
+    /// Начинает добавление вопроса в базу знаний: администратор выбирает
+    /// категорию, спускаясь по дереву клавиатурой, и пишет ответ
+    fn start_promotion(&self, conversation: &Conversation, chat_id: i64, user_id: u64, id: i64) -> Vec<Reply> {
+        let question = match saved_question(conversation, chat_id, id) {
+            Ok(question) => question,
+            Err(replies) => return replies,
+        };
+
+        let messages = conversation.messages();
+        let path = vec![];
+        let keyboard = browse_keyboard(messages, &conversation.knowledge().load(), &path);
+        self.wait(chat_id, user_id, Awaiting::Category { question_id: id, path });
+        vec![Reply::Message {
+            chat_id,
+            text: truncate(fill(&messages.admin_choose_category, &[("id", &id), ("question", &question.question)])),
+            keyboard: Some(keyboard),
+        }]
+    }
+
+    /// Шаг по дереву категорий: вглубь, назад или выбор текущей
+    fn choose_category(&self, conversation: &Conversation, chat_id: i64, user_id: u64, id: i64, mut path: Vec<String>, choice: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let qna = conversation.knowledge().load();
+        // Категорию могли удалить перезагрузкой базы, пока администратор выбирал
+        if qna.find_path(&path).is_none() {
+            path.clear();
+        }
+
+        match choice {
+            cancel if cancel == messages.button_cancel
+                => return vec![cancelled(&qna, chat_id, fill(&messages.admin_promotion_cancelled, &[("id", &id)]))],
+            here if here == messages.button_here && !path.is_empty() => {
+                let question = match saved_question(conversation, chat_id, id) {
+                    Ok(question) => question,
+                    Err(replies) => return replies,
+                };
+                let mut choices = vec![];
+                if question.answer.is_some() {
+                    choices.push(messages.button_keep_answer.clone());
+                }
+                choices.push(messages.button_cancel.clone());
+
+                let prompt = fill(&messages.admin_promoted_answer, &[("id", &id), ("category", &path.join(" / "))]);
+                self.wait(chat_id, user_id, Awaiting::PromotedAnswer { question_id: id, path });
+                return vec![Reply::Message { chat_id, text: prompt, keyboard: Some(Keyboard::Choices(choices)) }];
+            }
+            up if up == messages.button_up => {
+                path.pop();
+            }
+            category => {
+                let current = qna.find_path(&path).unwrap_or_default();
+                if qna.get_categories(current).iter().any(|known| known == category) {
+                    path.push(category.to_string());
+                }
+            }
+        }
+
+        let keyboard = browse_keyboard(messages, &qna, &path);
+        let location = location(messages, &path);
+        self.wait(chat_id, user_id, Awaiting::Category { question_id: id, path });
+        vec![Reply::Message { chat_id, text: location, keyboard: Some(keyboard) }]
+    }
+
+    /// Добавляет вопрос в базу знаний и сразу показывает его всем пользователям.
+    /// Автор вопроса получает ответ, если ещё не получил такой же
+    fn promote(&self, conversation: &Conversation, chat_id: i64, id: i64, path: &[String], answer: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let question = match saved_question(conversation, chat_id, id) {
+            Ok(question) => question,
+            Err(replies) => return replies,
+        };
+        let answer = match answer {
+            cancel if cancel == messages.button_cancel => {
+                let cancelled_text = fill(&messages.admin_promotion_cancelled, &[("id", &id)]);
+                return vec![cancelled(&conversation.knowledge().load(), chat_id, cancelled_text)];
+            }
+            keep if keep == messages.button_keep_answer && question.answer.is_some() => question.answer.clone().unwrap_or_default(),
+            answer => answer.trim().to_string(),
+        };
+
+        let _editing = self.editing.lock().unwrap();
+        // Дописываем в файл, а не в базу из памяти, чтобы не затереть его правки
+        let mut qna = match QASerde::new().build(&self.knowledge_file) {
+            Ok(qna) => qna,
+            Err(error) => return vec![text(chat_id, fill(&messages.admin_knowledge_failed, &[("error", &error)]))],
+        };
+        let Some(category) = qna.find_path(path) else {
+            return vec![text(chat_id, fill(&messages.admin_category_gone, &[("category", &path.join(" / "))]))];
+        };
+        let label = question.question.trim().to_string();
+        if qna.add_question(category, label.clone(), answer.clone()).is_err() {
+            return vec![text(chat_id, fill(&messages.admin_already_known, &[("question", &label)]))];
+        }
+        if let Err(error) = snapshot::persist(&qna, &self.knowledge_file, &self.snapshot_file) {
+            return vec![text(chat_id, fill(&messages.admin_knowledge_failed, &[("error", &error)]))];
+        }
+        let keyboard = qna.get_children(None).ok().map(Keyboard::Choices);
+        conversation.knowledge().swap(qna);
+
+        let mut replies = vec![];
+        if question.status != QuestionStatus::Answered || question.answer.as_deref() != Some(answer.as_str()) {
+            replies = Self::answer(conversation, chat_id, id, &answer);
+        }
+        replies.push(Reply::Message {
+            chat_id,
+            text: fill(&messages.admin_promoted, &[("id", &id), ("category", &path.join(" / ")), ("question", &label)]),
+            keyboard,
+        });
+        replies
+    }
+
+    /// Начинает отметку вопроса как повтора: администратор находит в дереве
+    /// вопрос базы знаний, и автор получает ответ на него
+    fn start_duplicate(&self, conversation: &Conversation, chat_id: i64, user_id: u64, id: i64) -> Vec<Reply> {
+        let question = match saved_question(conversation, chat_id, id) {
+            Ok(question) => question,
+            Err(replies) => return replies,
+        };
+
+        let messages = conversation.messages();
+        let path = vec![];
+        let keyboard = original_keyboard(messages, &conversation.knowledge().load(), &path);
+        self.wait(chat_id, user_id, Awaiting::Original { question_id: id, path });
+        vec![Reply::Message {
+            chat_id,
+            text: truncate(fill(&messages.admin_choose_original, &[("id", &id), ("question", &question.question)])),
+            keyboard: Some(keyboard),
+        }]
+    }
+
+    /// Шаг по дереву в поисках исходного вопроса: вглубь, назад или выбор вопроса
+    fn choose_original(&self, conversation: &Conversation, chat_id: i64, user_id: u64, id: i64, mut path: Vec<String>, choice: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let qna = conversation.knowledge().load();
+        if qna.find_path(&path).is_none() {
+            path.clear();
+        }
+        let current = qna.find_path(&path).unwrap_or_default();
+
+        match choice {
+            cancel if cancel == messages.button_cancel
+                => return vec![cancelled(&qna, chat_id, fill(&messages.admin_duplicate_cancelled, &[("id", &id)]))],
+            up if up == messages.button_up => {
+                path.pop();
+            }
+            choice if qna.get_categories(current).iter().any(|known| known == choice) => path.push(choice.to_string()),
+            choice => {
+                let answer = qna.tree()
+                                .get_children_by_id(current)
+                                .unwrap_or_default()
+                                .into_iter()
+                                .filter_map(|child| qna.get_node(child))
+                                .find(|node| node.is_question() && node.label == choice)
+                                .and_then(|node| node.answer.clone());
+                if let Some(answer) = answer {
+                    let mut replies = match Self::send_answer(conversation, chat_id, id, QuestionStatus::Duplicate, &answer) {
+                        Ok(replies) => replies,
+                        Err(replies) => return replies,
+                    };
+                    replies.push(Reply::Message {
+                        chat_id,
+                        text: fill(&messages.admin_duplicate, &[("id", &id), ("original", &choice)]),
+                        keyboard: qna.get_children(None).ok().map(Keyboard::Choices),
+                    });
+                    return replies;
+                }
+            }
+        }
+
+        let keyboard = original_keyboard(messages, &qna, &path);
+        let location = location(messages, &path);
+        self.wait(chat_id, user_id, Awaiting::Original { question_id: id, path });
+        vec![Reply::Message { chat_id, text: location, keyboard: Some(keyboard) }]
+    }
+
+    fn broadcast(conversation: &Conversation, chat_id: i64, message: &str) -> Vec<Reply> {
+        let messages = conversation.messages();
+        if message.is_empty() {
+            return vec![text(chat_id, messages.usage_broadcast.clone())];
+        }
+        let chats = match conversation.question_db().chats() {
+            Ok(chats) => chats,
+            Err(error) => return vec![text(chat_id, fill(&messages.admin_error, &[("error", &error)]))],
+        };
+
+        let confirmation = text(chat_id, fill(&messages.admin_broadcast, &[("count", &chats.len())]));
+        chats.into_iter()
+             .map(|chat| text(chat, message.to_string()))
+             .chain([confirmation])
+             .collect()
+    }
+}
+
+/// Вопрос на модерации так, как его видит администратор
+fn describe(messages: &Messages, question: &SavedQuestion) -> String {
+    let author = match &question.username {
+        Some(username) => format!("@{username}"),
+        None => format!("id {}", question.user_id),
+    };
+    let mut details = String::new();
+    if question.status == QuestionStatus::Approved {
+        details.push_str(&messages.admin_question_approved);
+    }
+    // Для русского "2 раза", но "12 раз"
+    let asked = match (question.asked % 10, question.asked % 100) {
+        _ if question.asked <= 1 => None,
+        (2..=4, tens) if !(12..=14).contains(&tens) => Some(&messages.admin_question_asked_few),
+        _ => Some(&messages.admin_question_asked),
+    };
+    if let Some(asked) = asked {
+        details.push_str(&fill(asked, &[("count", &question.asked)]));
+    }
+
+    let text = match question.unhelpful {
+        true => messages.feedback_question.replace("{question}", &question.question),
+        false => question.question.clone(),
+    };
+
+    fill(&messages.admin_question, &[
+        ("id", &question.id),
+        ("author", &author),
+        ("date", &question.created_at),
+        ("details", &details),
+        ("question", &text),
+    ])
+}
+
+/// Вопрос базы знаний (название и ответ), похожий на сохранённый. Ответ,
+/// который автор уже оценил как бесполезный, не предлагается
+fn similar_known(conversation: &Conversation, question: &SavedQuestion) -> Option<(String, String)> {
+    let qna = conversation.knowledge().load();
+    let known = qna.find_similar(&question.question)?;
+    if matches!(conversation.question_db().vote(&known.label, question.user_id), Ok(Some(false))) {
+        return None;
+    }
+    Some((known.label.clone(), known.answer.clone().unwrap_or_default()))
+}
+
+/// Текст и кнопки вопроса на модерации с подсказкой о похожем вопросе базы знаний
+fn moderation_message(conversation: &Conversation, question: &SavedQuestion) -> (String, Keyboard) {
+    let messages = conversation.messages();
+    let mut text = describe(messages, question);
+    // Похожий вопрос только подсказывается: похожие формулировки бывают и у разных вопросов
+    let similar = similar_known(conversation, question);
+    if let Some((similar, _)) = &similar {
+        text.push('\n');
+        text.push_str(&fill(&messages.admin_similar, &[("question", similar)]));
+    }
+
+    (text, moderation_keyboard(messages, question, similar.is_some()))
+}
+
+fn moderation_keyboard(messages: &Messages, question: &SavedQuestion, similar: bool) -> Keyboard {
+    let button = |text: &str, action: &str| InlineButton {
+        text: text.to_string(),
+        data: format!("{MODERATION_PREFIX}{action}:{}", question.id),
+    };
+
+    let mut buttons = vec![];
+    if similar {
+        buttons.push(button(&messages.button_similar, "similar"));
+    }
+    if question.status != QuestionStatus::Approved {
+        buttons.push(button(&messages.button_approve, "approve"));
+    }
+    buttons.push(button(&messages.button_reject, "reject"));
+    buttons.push(button(&messages.button_answer, "answer"));
+    buttons.push(button(&messages.button_duplicate, "duplicate"));
+    buttons.push(button(&messages.button_promote, "promote"));
+
+    Keyboard::Inline(buttons)
+}
+
+/// Сохранённый вопрос или сообщение администратору, почему его нет
+fn saved_question(conversation: &Conversation, chat_id: i64, id: i64) -> Result<SavedQuestion, Vec<Reply>> {
+    let messages = conversation.messages();
+    match conversation.question_db().question(id) {
+        Ok(Some(question)) => Ok(question),
+        Ok(None) => Err(vec![text(chat_id, fill(&messages.admin_not_found, &[("id", &id)]))]),
+        Err(error) => Err(vec![text(chat_id, fill(&messages.admin_question_failed, &[("id", &id), ("error", &error)]))]),
+    }
+}
+
+/// Где администратор находится в дереве категорий
+fn location(messages: &Messages, path: &[String]) -> String {
+    match path.is_empty() {
+        true => messages.admin_root.clone(),
+        false => fill(&messages.admin_category, &[("category", &path.join(" / "))]),
+    }
+}
+
+/// Подкатегории текущей категории и кнопки управления
+fn browse_keyboard(messages: &Messages, qna: &QASerde, path: &[String]) -> Keyboard {
+    let mut choices = qna.find_path(path).map(|id| qna.get_categories(id)).unwrap_or_default();
+    if !path.is_empty() {
+        choices.push(messages.button_here.clone());
+        choices.push(messages.button_up.clone());
+    }
+    choices.push(messages.button_cancel.clone());
+
+    Keyboard::Choices(choices)
+}
+
+/// Всё содержимое текущей категории: подкатегории и вопросы
+fn original_keyboard(messages: &Messages, qna: &QASerde, path: &[String]) -> Keyboard {
+    let mut choices = qna.find_path(path)
+                         .and_then(|id| qna.tree().get_children_by_id(id))
+                         .unwrap_or_default()
+                         .into_iter()
+                         .filter_map(|id| qna.get_node(id))
+                         .map(|node| node.label.clone())
+                         .collect::<Vec<_>>();
+    if !path.is_empty() {
+        choices.push(messages.button_up.clone());
+    }
+    choices.push(messages.button_cancel.clone());
+
+    Keyboard::Choices(choices)
+}
+
+/// Отмена выбора в дереве: администратору возвращается обычная клавиатура
+fn cancelled(qna: &QASerde, chat_id: i64, text: String) -> Reply {
+    Reply::Message { chat_id, text, keyboard: qna.get_children(None).ok().map(Keyboard::Choices) }
+}
+
+fn text(chat_id: i64, text: String) -> Reply {
+    Reply::Message { chat_id, text, keyboard: None }
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(MESSAGE_LIMIT) {
+        text.truncate(cut);
+        text.push('…');
+    }
+    text
+}
@@ -14,13 +14,35 @@ pub struct Node<T: Debug> {
     pub data: T,
 }
 
+/// Содержимое ноды, которое можно найти по ключу, не сравнивая
+/// всю ноду целиком
+pub trait Keyed {
+    type Key: ?Sized + PartialEq;
+
+    fn key(&self) -> &Self::Key;
+}
+
+impl Keyed for String {
+    type Key = str;
+
+    fn key(&self) -> &str {
+        self
+    }
+}
+
 impl<Borrowed: Debug> Borrow<Borrowed> for Node<Borrowed> {
     fn borrow(&self) -> &Borrowed {
-        return &self.data
+        &self.data
     }
 }
 
-impl<T: Eq + Clone + Debug> Arena<T> {
+impl<T: Debug> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Debug> Arena<T> {
     pub fn new() -> Self {
         Arena { nodes: vec![] }
     }
@@ -34,19 +56,16 @@ impl<T: Eq + Clone + Debug> Arena<T> {
     }
 
     pub fn add_node(&mut self, data: T, parent: Option<NodeId>) -> Option<NodeId> {
-        dbg!("Добавляем ноду...");
         let new_index = self.nodes.len();
 
         if let Some(parent_id) = parent {
-            dbg!("Есть родитель...");
             if let Some(parent_node) = self.nodes.get_mut(parent_id) {
                 parent_node.children.push(new_index);
 
-                dbg!("Добавляем в массив");
                 self.nodes.push(Node {
-                    parent: Some(parent_id), 
-                    children: vec![], 
-                    data 
+                    parent: Some(parent_id),
+                    children: vec![],
+                    data
                 });
 
                 Some(new_index)
@@ -58,70 +77,57 @@ impl<T: Eq + Clone + Debug> Arena<T> {
                 data
             });
 
-            Some(0)
-        }         
+            Some(new_index)
+        }
 
     }
 
     pub fn get_parent(&self, node_id: NodeId) -> Option<NodeId> {
-        if let Some(node) = self.nodes.get(node_id) {
-            return node.parent
-        } else {
-            None
-        }
+        self.nodes.get(node_id).and_then(|node| node.parent)
     }
 
-    pub fn get_children_by_value(&self, node_data: T) -> Option<Vec<NodeId>> {
-        self.nodes.iter()
-                  .find(|node| node.data == node_data)
-                  .map( |node| node.children.clone())
+    pub fn get_children_by_id(&self, node_id: NodeId) -> Option<Vec<NodeId>> {
+        self.nodes.get(node_id).map(|node| node.children.clone())
     }
 
-    pub fn get_leaves_parents(&self) -> Vec<NodeId> {
-        self.nodes.iter()
-                  .filter(|node| node.children.is_empty())
-                  .map( |node| node.parent.unwrap())
-                  .collect()
+    pub fn get_root_id(&self) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.parent.is_none())
     }
 
-    pub fn get_children_by_id(&self, node_id: NodeId) -> Option<Vec<NodeId>> {
-        if let Some(node) = self.nodes.get(node_id) {
-            Some(node.children.clone())
-        } else {
-            None
-        }
+    pub fn get(&self, node_id: NodeId) -> Option<&T> {
+        self.nodes.get(node_id).map(|node| node.borrow())
     }
 
-    pub fn get_id_by_value(&self, node_data: T) -> Option<NodeId> {
-        self.nodes.iter()
-                  .enumerate()
-                  .find(|(_, node)| node.data == node_data)
-                  .map( |(index,_)| index)
+    pub fn get_mut(&mut self, node_id: NodeId) -> Option<&mut T> {
+        self.nodes.get_mut(node_id).map(|node| &mut node.data)
     }
 
-    pub fn get_root_value(&self) -> Option<T> {
-        if let Some(root) = self.nodes.iter().find(|n| n.parent.is_none()) {
-            Some(root.data.clone())
-        } else {
-            None
-        }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
     }
 
-    pub fn get(&self, node_id: NodeId) -> Option<&T> {
-        if let Some(node) = self.nodes.get(node_id) {
-            Some(node.borrow())
-        } else {
-            None
-        }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
     }
 
-    pub fn contains(&self, node_data: T) -> bool {
-        for val in &self.nodes {
-            if val.data == node_data {
-                return true;
-            }
-        }
+    /// Обход всех нод в порядке добавления вместе с их индексами
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.nodes.iter().enumerate().map(|(id, node)| (id, &node.data))
+    }
+}
+
+impl<T: Keyed + Debug> Arena<T> {
+    pub fn get_id_by_key(&self, key: &T::Key) -> Option<NodeId> {
+        self.nodes.iter()
+                  .position(|node| node.data.key() == key)
+    }
+
+    pub fn get_children_by_key(&self, key: &T::Key) -> Option<Vec<NodeId>> {
+        self.get_id_by_key(key)
+            .and_then(|id| self.get_children_by_id(id))
+    }
 
-        return false;
+    pub fn contains_key(&self, key: &T::Key) -> bool {
+        self.get_id_by_key(key).is_some()
     }
 }
@@ -0,0 +1,364 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::arenatree::NodeId;
+use crate::config::{Config, Mode};
+use crate::console::Console;
+use crate::conversation::Conversation;
+use crate::db::{Database, SavedQuestion};
+use crate::diff::TreeDiff;
+use crate::knowledge::KnowledgeBase;
+use crate::metrics::MetricsServer;
+use crate::report::Report;
+use crate::serde::{NodeKind, QASerde};
+use crate::telegram::TelegramSender;
+use crate::telegram_async::AsyncRunner;
+use crate::util::logging::{check_result, non_fatal};
+use crate::webhook::WebhookServer;
+use crate::scenario::Scenario;
+use crate::snapshot;
+
+/// Бот, отвечающий на вопросы по базе знаний
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Файл конфигурации (по умолчанию BOT_CONFIG или config.toml)
+    #[arg(long, global = true)]
+    config: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Запустить бота (команда по умолчанию)
+    Run,
+    /// Проверить файл базы знаний
+    Validate { qna_file: String },
+    /// Показать дерево базы знаний
+    Tree { qna_file: String },
+    /// Показать изменения между двумя версиями базы знаний
+    Diff { old_file: String, new_file: String },
+    /// Собрать снапшот базы знаний для быстрого запуска
+    Snapshot { qna_file: String, snapshot_file: String },
+    /// Выгрузить вопросы, сохранённые пользователями
+    ExportSuggestions {
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Файл для выгрузки (по умолчанию стандартный вывод)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Показать статистику бота за период
+    Stats {
+        /// Первый день периода, ГГГГ-ММ-ДД (по умолчанию - неделя до --to)
+        #[arg(long, value_parser = parse_date)]
+        from: Option<String>,
+        /// Последний день периода, ГГГГ-ММ-ДД (по умолчанию сегодня, UTC)
+        #[arg(long, value_parser = parse_date)]
+        to: Option<String>,
+        /// Вывести таблицей CSV вместо текста
+        #[arg(long)]
+        csv: bool,
+        /// Файл для отчёта (по умолчанию стандартный вывод)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Загрузить вопросы, выгруженные export-suggestions в формате JSON
+    Import { file: String },
+    /// Обновить схему базы вопросов
+    Migrate,
+    /// Поговорить с ботом в терминале, без Telegram
+    Console {
+        /// Файл базы знаний (по умолчанию из конфигурации)
+        qna_file: Option<String>,
+    },
+    /// Проверить базу знаний сценариями разговоров
+    Scenarios {
+        qna_file: String,
+        #[arg(required = true)]
+        scenario_files: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+impl Cli {
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        let config_path = self.config.as_deref();
+
+        match self.command.unwrap_or(Command::Run) {
+            Command::Run => run_bot(config_path),
+            Command::Validate { qna_file } => validate(&qna_file),
+            Command::Tree { qna_file } => {
+                let qna = QASerde::new().build(&qna_file)?;
+                print_tree(&qna, &mut io::stdout().lock())?;
+                Ok(())
+            }
+            Command::Diff { old_file, new_file } => {
+                let old = QASerde::new().build(&old_file)?;
+                let new = QASerde::new().build(&new_file)?;
+                print!("{}", TreeDiff::compute(&old, &new));
+                Ok(())
+            }
+            Command::Snapshot { qna_file, snapshot_file } => {
+                let qna = snapshot::build(&qna_file, &snapshot_file)?;
+                println!("Снапшот записан: {} нод", qna.tree().len());
+                Ok(())
+            }
+            Command::ExportSuggestions { format, output } => {
+                let db = open_database(config_path)?;
+                let questions = db.questions()?;
+                let mut out: Box<dyn Write> = match output {
+                    Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                    None       => Box::new(io::stdout().lock()),
+                };
+                match format {
+                    Format::Csv  => write_csv(&questions, &mut out)?,
+                    Format::Json => serde_json::to_writer_pretty(&mut out, &questions)?,
+                }
+                out.flush()?;
+                Ok(())
+            }
+            Command::Stats { from, to, csv, output } => {
+                let config = Config::load_unchecked(config_path)?;
+                let db = Database::new(&config.paths.questions_db)?;
+                let (week_start, to) = db.period(to.as_deref(), 7)?;
+                let from = from.unwrap_or(week_start);
+                // Даты в формате ГГГГ-ММ-ДД сравниваются как строки
+                if from > to {
+                    return Err(format!("--from {from} позже конца периода {to}").into());
+                }
+                let report = Report::build(&db, &from, &to)?;
+                let mut out: Box<dyn Write> = match output {
+                    Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                    None       => Box::new(io::stdout().lock()),
+                };
+                if csv {
+                    report.write_csv(&mut out)?;
+                } else {
+                    writeln!(out, "{}", report.render(&config.messages))?;
+                }
+                out.flush()?;
+                Ok(())
+            }
+            Command::Import { file } => {
+                let questions: Vec<SavedQuestion> = serde_json::from_reader(File::open(file)?)?;
+                let imported = open_database(config_path)?.import_questions(&questions)?;
+                println!("Загружено вопросов: {imported} из {}", questions.len());
+                Ok(())
+            }
+            Command::Migrate => {
+                let config = Config::load_unchecked(config_path)?;
+                let db = Database::open(&config.paths.questions_db);
+                let (before, _) = db.schema_version()?;
+                let applied = db.migrate()?;
+                println!("Схема базы вопросов: версия {before} -> {}", before + applied);
+                Ok(())
+            }
+            Command::Console { qna_file } => run_console(config_path, qna_file),
+            Command::Scenarios { qna_file, scenario_files } => run_scenarios(config_path, &qna_file, &scenario_files),
+        }
+    }
+}
+
+fn run_bot(config_path: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let config = match Config::load(config_path) {
+        Ok(config) => config,
+        Err(error) => {
+            non_fatal(error);
+            std::process::exit(1);
+        }
+    };
+
+    let suggestions_db = Database::new(&config.paths.questions_db)?;
+    let qna = snapshot::load_or_build(&config.paths.knowledge_base, &config.paths.snapshot)?;
+    let knowledge = KnowledgeBase::new(qna);
+    if let Some(listen) = &config.metrics_listen {
+        // В режиме вебхука getUpdates не вызывается, и зависание опроса не проверить
+        let stall_after = (config.mode != Mode::Webhook).then(|| Duration::from_secs(2 * u64::from(config.polling.timeout) + 60));
+        MetricsServer::bind(listen, knowledge.clone(), stall_after)?.spawn();
+    }
+    // В асинхронном режиме и ответы отправляются в задачах его рантайма
+    let runtime = (config.mode == Mode::Async).then(tokio::runtime::Runtime::new).transpose()?;
+    let bot = TelegramSender::new(&config, knowledge, suggestions_db, runtime.as_ref().map(|runtime| runtime.handle().clone()));
+    bot.register_commands();
+
+    match (config.mode, runtime) {
+        // Разные чаты обрабатываются параллельно
+        (Mode::Async, Some(runtime)) => {
+            let runner = AsyncRunner::new(&config.bot_api_url(), Arc::new(bot));
+            runtime.block_on(runner.start_reply_loop());
+        }
+        (Mode::Webhook, _) => {
+            check_result(bot.set_webhook(&config.webhook.url, &config.webhook.secret), non_fatal);
+            let server = WebhookServer::bind(&config.webhook.listen, &config.webhook.secret)?;
+            server.start_reply_loop(&bot);
+        }
+        _ => bot.start_reply_loop(),
+    }
+
+    Ok(())
+}
+
+fn run_console(config_path: Option<&str>, qna_file: Option<String>) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_unchecked(config_path)?;
+    let qna = QASerde::new().build(qna_file.as_deref().unwrap_or(&config.paths.knowledge_base))?;
+
+    let db_path = scratch_database_path();
+    let conversation = Conversation::new(KnowledgeBase::new(qna), config.messages, Database::new(&db_path)?);
+
+    let result = Console::new(&conversation, io::stdout().lock()).run(io::stdin().lock());
+    let _ = std::fs::remove_file(&db_path);
+
+    Ok(result?)
+}
+
+fn run_scenarios(config_path: Option<&str>, qna_file: &str, scenario_files: &[String]) -> Result<(), Box<dyn Error>> {
+    let config = Config::load_unchecked(config_path)?;
+    let knowledge = KnowledgeBase::new(QASerde::new().build(qna_file)?);
+    let scenarios = scenario_files.iter()
+                                  .map(|path| Scenario::load(path))
+                                  .collect::<Result<Vec<_>, _>>()?;
+
+    let db_path = scratch_database_path();
+    let mut failed = 0;
+    for scenario in &scenarios {
+        // Каждый сценарий начинается с чистого разговора
+        let conversation = Conversation::new(knowledge.clone(), config.messages.clone(), Database::new(&db_path)?);
+        let mismatches = scenario.run(&conversation);
+
+        if mismatches.is_empty() {
+            println!("{}: пройден", scenario.name);
+        } else {
+            failed += 1;
+            println!("{}: провален", scenario.name);
+            for mismatch in mismatches {
+                println!("  - {mismatch}");
+            }
+        }
+    }
+    let _ = std::fs::remove_file(&db_path);
+
+    if failed > 0 {
+        eprintln!("Провалено сценариев: {failed} из {}", scenarios.len());
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Временная база вопросов: сохранённое в консоли и сценариях
+/// не должно попасть в настоящую
+fn scratch_database_path() -> String {
+    let path = std::env::temp_dir().join(format!("mr-deeds-scratch-{}.db", std::process::id()));
+    path.to_string_lossy().into_owned()
+}
+
+/// База вопросов по пути из конфигурации, с актуальной схемой
+fn open_database(config_path: Option<&str>) -> Result<Database, Box<dyn Error>> {
+    let config = Config::load_unchecked(config_path)?;
+    Database::new(&config.paths.questions_db)
+}
+
+fn validate(qna_file: &str) -> Result<(), Box<dyn Error>> {
+    let qna = QASerde::new().build(qna_file)?;
+    let problems = qna.validate();
+
+    if problems.is_empty() {
+        println!("{qna_file}: ошибок нет, вопросов: {}", qna.question_id.len());
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    eprintln!("{qna_file}: найдено проблем: {}", problems.len());
+    std::process::exit(1);
+}
+
+fn print_tree(qna: &QASerde, out: &mut impl Write) -> io::Result<()> {
+    let Some(root) = qna.tree().get_root_id() else {
+        return Ok(());
+    };
+    if let Some(node) = qna.get_node(root) {
+        writeln!(out, "{}", node.label)?;
+    }
+    print_children(qna, root, "", out)
+}
+
+fn print_children(qna: &QASerde, parent: NodeId, prefix: &str, out: &mut impl Write) -> io::Result<()> {
+    let children = qna.tree().get_children_by_id(parent).unwrap_or_default();
+
+    for (position, &child) in children.iter().enumerate() {
+        let Some(node) = qna.get_node(child) else { continue };
+        let last = position + 1 == children.len();
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+        match node.kind {
+            NodeKind::Question => writeln!(out, "{prefix}{branch}{} -> {}", node.label, node.answer.as_deref().unwrap_or(""))?,
+            _ => writeln!(out, "{prefix}{branch}{}", node.label)?,
+        }
+        print_children(qna, child, &format!("{prefix}{indent}"), out)?;
+    }
+
+    Ok(())
+}
+
+/// Проверяет дату ГГГГ-ММ-ДД: SQLite молча не понимает другие форматы
+fn parse_date(value: &str) -> Result<String, String> {
+    let invalid = || format!("неверная дата {value}, ожидается ГГГГ-ММ-ДД");
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 || !value.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return Err(invalid());
+    }
+
+    let (year, month, day): (u32, u32, u32) = (year.parse().map_err(|_| invalid())?,
+                                               month.parse().map_err(|_| invalid())?,
+                                               day.parse().map_err(|_| invalid())?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if !(1..=days).contains(&day) {
+        return Err(invalid());
+    }
+
+    Ok(value.to_string())
+}
+
+fn write_csv(questions: &[SavedQuestion], out: &mut impl Write) -> io::Result<()> {
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+
+    writeln!(out, "id,user_id,chat_id,username,question,status,answer,created_at,updated_at")?;
+    for question in questions {
+        writeln!(out, "{},{},{},{},{},{},{},{},{}",
+                 question.id,
+                 question.user_id,
+                 question.chat_id,
+                 question.username.as_deref().unwrap_or(""),
+                 quote(&question.question),
+                 question.status.as_str(),
+                 quote(question.answer.as_deref().unwrap_or("")),
+                 question.created_at,
+                 question.updated_at)?;
+    }
+
+    Ok(())
+}
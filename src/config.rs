@@ -0,0 +1,1046 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::config::ConfigError;
+use crate::polling::PollingConfig;
+
+/// Как бот получает обновления
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Long polling, обновления обрабатываются по одному
+    Polling,
+    /// Long polling асинхронным клиентом, чаты обрабатываются параллельно
+    Async,
+    /// Telegram присылает обновления на встроенный HTTP-сервер
+    Webhook,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Paths {
+    /// SQLite-база с сохранёнными вопросами пользователей
+    pub questions_db: String,
+    /// JSON-файл базы знаний
+    pub knowledge_base: String,
+    /// Собранный снапшот базы знаний
+    pub snapshot: String,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Paths {
+            questions_db: "questions.db".to_string(),
+            knowledge_base: "db.json".to_string(),
+            snapshot: "db.snapshot".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Polling {
+    /// Сколько секунд Telegram держит запрос getUpdates
+    pub timeout: u32,
+}
+
+impl Default for Polling {
+    fn default() -> Self {
+        Polling { timeout: PollingConfig::default().timeout }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Webhook {
+    /// Публичный адрес, на который Telegram будет присылать обновления
+    pub url: String,
+    /// Адрес, на котором слушает встроенный сервер
+    pub listen: String,
+    /// Секрет из заголовка X-Telegram-Bot-Api-Secret-Token: без него
+    /// обновления мог бы прислать любой, кто узнал адрес
+    pub secret: String,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Webhook { url: String::new(), listen: "0.0.0.0:8443".to_string(), secret: String::new() }
+    }
+}
+
+/// Тексты, которые бот показывает пользователям
+#[derive(Debug, Clone)]
+pub struct Messages {
+    pub start: String,
+    pub help: String,
+    pub reset: String,
+    pub unknown_command: String,
+    /// `{category}` заменяется на название категории
+    pub category: String,
+    pub not_found: String,
+    pub text_only: String,
+    pub choice_saved: String,
+    pub button_yes: String,
+    pub button_no: String,
+    pub command_start: String,
+    pub command_reset: String,
+    pub command_help: String,
+    /// Ответ на команду администратора от обычного пользователя
+    pub admin_only: String,
+    /// Ответ администратора на сохранённый вопрос;
+    /// `{question}` и `{answer}` заменяются на вопрос и ответ
+    pub admin_answer: String,
+    pub button_operator: String,
+    pub command_operator: String,
+    /// Пользователь подключён к оператору
+    pub operator_connected: String,
+    /// Кнопка и ответ на завершение разговора с оператором
+    pub operator_close: String,
+    pub operator_closed: String,
+    /// Сообщение оператора пользователю; `{text}` заменяется на текст оператора
+    pub operator_reply: String,
+    /// Операторы не настроены
+    pub operator_unavailable: String,
+    /// Обращение в чате операторов: `{id}` - номер, `{author}` - пользователь
+    pub operator_ticket: String,
+    /// Сообщение пользователя, пересланное в чат операторов
+    pub operator_ticket_message: String,
+    pub button_close_ticket: String,
+    pub operator_bad_button: String,
+    /// Ошибка при закрытии обращения; `{error}` заменяется на её текст
+    pub operator_error: String,
+    pub operator_not_found: String,
+    pub operator_already_closed: String,
+    pub operator_closed_by_user: String,
+    pub operator_closed_by_operator: String,
+    /// Вопрос под ответом из базы знаний и кнопки оценки
+    pub feedback_prompt: String,
+    pub button_helpful: String,
+    pub button_unhelpful: String,
+    pub feedback_thanks: String,
+    /// Предложение сохранить вопрос после 👎
+    pub feedback_save: String,
+    /// Как модераторы и операторы видят вопрос, сохранённый после 👎;
+    /// `{question}` заменяется на вопрос из базы знаний
+    pub feedback_question: String,
+    /// Описания команд администраторов в меню
+    pub command_reload: String,
+    pub command_stats: String,
+    pub command_pending: String,
+    pub command_answer: String,
+    pub command_broadcast: String,
+    pub command_promote: String,
+    pub command_feedback: String,
+    /// Подсказки к командам администраторов; `{max}` - наибольшее число дней
+    pub usage_stats: String,
+    pub usage_pending: String,
+    pub usage_answer: String,
+    pub usage_broadcast: String,
+    pub usage_promote: String,
+    /// Кнопки модерации под вопросом
+    pub button_approve: String,
+    pub button_reject: String,
+    pub button_answer: String,
+    pub button_duplicate: String,
+    pub button_promote: String,
+    pub button_similar: String,
+    /// Кнопки выбора категории или вопроса в дереве базы знаний
+    pub button_here: String,
+    pub button_up: String,
+    pub button_cancel: String,
+    pub button_keep_answer: String,
+    pub admin_bad_button: String,
+    /// Ошибка базы или файла; `{error}` заменяется на её текст
+    pub admin_error: String,
+    /// Дальше `{id}` заменяется на номер сохранённого вопроса
+    pub admin_not_found: String,
+    pub admin_approved: String,
+    pub admin_rejected: String,
+    /// Вопрос на модерации; `{details}` - пометки о статусе и повторах
+    pub admin_question: String,
+    pub admin_question_approved: String,
+    /// Сколько раз спрашивали вопрос: для 2-4 (кроме 12-14) и для остальных чисел
+    pub admin_question_asked: String,
+    pub admin_question_asked_few: String,
+    pub admin_new_question: String,
+    pub admin_answer_prompt: String,
+    pub admin_answer_cancelled: String,
+    /// Администратор не ответил за `admin_timeout`
+    pub admin_expired: String,
+    pub admin_answer_saved: String,
+    pub admin_answer_saved_repeats: String,
+    pub admin_answer_failed: String,
+    pub admin_question_failed: String,
+    pub admin_reload_failed: String,
+    pub admin_reloaded: String,
+    pub admin_no_changes: String,
+    pub admin_changes: String,
+    pub admin_problems: String,
+    pub admin_stats: String,
+    pub admin_report_failed: String,
+    pub admin_export_failed: String,
+    pub admin_no_votes: String,
+    /// Заголовок и строка отчёта об оценках ответов
+    pub admin_votes: String,
+    pub admin_vote: String,
+    pub admin_no_pending: String,
+    pub admin_pending: String,
+    pub admin_next_page: String,
+    pub admin_choose_category: String,
+    /// Где администратор находится в дереве базы знаний
+    pub admin_root: String,
+    pub admin_category: String,
+    pub admin_promoted_answer: String,
+    pub admin_promotion_cancelled: String,
+    pub admin_category_gone: String,
+    pub admin_already_known: String,
+    pub admin_knowledge_failed: String,
+    pub admin_promoted: String,
+    pub admin_choose_original: String,
+    pub admin_duplicate_cancelled: String,
+    pub admin_duplicate: String,
+    /// Подсказка под вопросом на модерации: в базе знаний есть похожий
+    pub admin_similar: String,
+    pub admin_no_similar: String,
+    pub admin_broadcast: String,
+    /// Отчёт /stats; `{from}`, `{to}`, `{count}` и `{time}` заменяются на значения
+    pub report_title: String,
+    pub report_daily_users: String,
+    pub report_messages: String,
+    pub report_top_questions: String,
+    pub report_top_categories: String,
+    pub report_unmatched: String,
+    /// Пустой список в отчёте
+    pub report_empty: String,
+    pub report_pending: String,
+    pub report_answer_time: String,
+    pub report_no_answers: String,
+    /// Длительности: меньше минуты, меньше часа и дольше
+    pub report_seconds: String,
+    pub report_minutes: String,
+    pub report_hours: String,
+}
+
+impl Messages {
+    pub fn for_language(language: &str) -> Option<Self> {
+        let texts = match language {
+            "ru" => Messages {
+                start: "Привет! Я бот!".to_string(),
+                help: "Выбери вопрос или напиши свой".to_string(),
+                reset: "Вернулись в начало.".to_string(),
+                unknown_command: "Неизвестная команда".to_string(),
+                category: "Категория: \"{category}\"".to_string(),
+                not_found: "Прости, я не знаю ответа на твой вопрос...".to_string(),
+                text_only: "Извини, я понимаю только вопросы текстом!".to_string(),
+                choice_saved: "Я сохранил твой выбор, спасибо!".to_string(),
+                button_yes: "Да".to_string(),
+                button_no: "Нет".to_string(),
+                command_start: "Начать работу".to_string(),
+                command_reset: "Вернуться в начало".to_string(),
+                command_help: "Помощь".to_string(),
+                admin_only: "Эта команда доступна только администраторам".to_string(),
+                admin_answer: "Ответ на ваш вопрос \"{question}\":\n{answer}".to_string(),
+                button_operator: "Связаться с оператором".to_string(),
+                command_operator: "Связаться с оператором".to_string(),
+                operator_connected: "Вы подключены к оператору. Пишите, оператор ответит здесь.".to_string(),
+                operator_close: "Завершить разговор".to_string(),
+                operator_closed: "Разговор с оператором завершён.".to_string(),
+                operator_reply: "Оператор: {text}".to_string(),
+                operator_unavailable: "Сейчас операторы недоступны.".to_string(),
+                operator_ticket: "Обращение #{id} от {author}".to_string(),
+                operator_ticket_message: "#{id} {author}: {text}".to_string(),
+                button_close_ticket: "Закрыть обращение".to_string(),
+                operator_bad_button: "Неверная кнопка".to_string(),
+                operator_error: "Ошибка: {error}".to_string(),
+                operator_not_found: "Обращение #{id} не найдено".to_string(),
+                operator_already_closed: "Обращение #{id} уже закрыто".to_string(),
+                operator_closed_by_user: "Обращение #{id} закрыто пользователем".to_string(),
+                operator_closed_by_operator: "Обращение #{id} закрыто оператором".to_string(),
+                feedback_prompt: "Ответ помог?".to_string(),
+                button_helpful: "👍".to_string(),
+                button_unhelpful: "👎".to_string(),
+                feedback_thanks: "Спасибо за оценку!".to_string(),
+                feedback_save: "Жаль! Сохранить вопрос, чтобы на него ответил человек?".to_string(),
+                feedback_question: "Не помог ответ на вопрос \"{question}\"".to_string(),
+                command_reload: "Перечитать базу знаний".to_string(),
+                command_stats: "Статистика бота: /stats [дней] [csv]".to_string(),
+                command_pending: "Вопросы пользователей на модерации: /pending [страница]".to_string(),
+                command_answer: "Ответить на вопрос: /answer <номер> <ответ>".to_string(),
+                command_broadcast: "Сообщение всем пользователям: /broadcast <текст>".to_string(),
+                command_promote: "Добавить вопрос в базу знаний: /promote <номер>".to_string(),
+                command_feedback: "Оценки ответов базы знаний".to_string(),
+                usage_stats: "Использование: /stats [дней, до {max}] [csv]".to_string(),
+                usage_pending: "Использование: /pending [страница]".to_string(),
+                usage_answer: "Использование: /answer <номер> <ответ>".to_string(),
+                usage_broadcast: "Использование: /broadcast <текст>".to_string(),
+                usage_promote: "Использование: /promote <номер>".to_string(),
+                button_approve: "Одобрить".to_string(),
+                button_reject: "Отклонить".to_string(),
+                button_answer: "Ответить".to_string(),
+                button_duplicate: "Повтор".to_string(),
+                button_promote: "В базу знаний".to_string(),
+                button_similar: "Да, это повтор".to_string(),
+                button_here: "✅ Сюда".to_string(),
+                button_up: "⬆️ Назад".to_string(),
+                button_cancel: "❌ Отмена".to_string(),
+                button_keep_answer: "Оставить прежний ответ".to_string(),
+                admin_bad_button: "Неверная кнопка".to_string(),
+                admin_error: "Ошибка: {error}".to_string(),
+                admin_not_found: "Вопрос #{id} не найден".to_string(),
+                admin_approved: "Вопрос #{id} одобрен".to_string(),
+                admin_rejected: "Вопрос #{id} отклонён".to_string(),
+                admin_question: "#{id} от {author}, {date}{details}\n{question}".to_string(),
+                admin_question_approved: ", одобрен".to_string(),
+                admin_question_asked: ", спрашивали {count} раз".to_string(),
+                admin_question_asked_few: ", спрашивали {count} раза".to_string(),
+                admin_new_question: "Новый вопрос {question}".to_string(),
+                admin_answer_prompt: "Напишите ответ на вопрос #{id} одним сообщением:\n{question}".to_string(),
+                admin_answer_cancelled: "Ответ на вопрос #{id} отменён".to_string(),
+                admin_expired: "Время ожидания истекло, действие отменено. Сообщение не сохранено.".to_string(),
+                admin_answer_saved: "Ответ на вопрос #{id} сохранён и отправляется автору".to_string(),
+                admin_answer_saved_repeats: "Ответ на вопрос #{id} сохранён и отправляется автору и ещё {others} спросившим".to_string(),
+                admin_answer_failed: "Не удалось сохранить ответ на вопрос #{id}: {error}".to_string(),
+                admin_question_failed: "Не удалось прочитать вопрос #{id}: {error}".to_string(),
+                admin_reload_failed: "Не удалось перечитать базу знаний, работаем со старой: {error}".to_string(),
+                admin_reloaded: "База знаний перезагружена, вопросов: {count}".to_string(),
+                admin_no_changes: "Изменений нет.".to_string(),
+                admin_changes: "Изменений: {count}".to_string(),
+                admin_problems: "Проблемы:".to_string(),
+                admin_stats: "Вопросов в базе знаний: {questions}\nСохранённых вопросов пользователей: {saved}\nЧатов: {chats}".to_string(),
+                admin_report_failed: "Не удалось собрать отчёт: {error}".to_string(),
+                admin_export_failed: "Не удалось выгрузить отчёт: {error}".to_string(),
+                admin_no_votes: "Ответы пока никто не оценивал.".to_string(),
+                admin_votes: "Оценки ответов, сначала худшие:".to_string(),
+                admin_vote: "{score}% 👍 ({helpful} 👍, {unhelpful} 👎) {question}".to_string(),
+                admin_no_pending: "Вопросов на модерации нет.".to_string(),
+                admin_pending: "Вопросов на модерации: {total}, страница {page} из {pages}".to_string(),
+                admin_next_page: "Следующая: /pending {page}".to_string(),
+                admin_choose_category: "Выберите категорию для вопроса #{id}:\n{question}".to_string(),
+                admin_root: "Корень базы знаний".to_string(),
+                admin_category: "Категория \"{category}\"".to_string(),
+                admin_promoted_answer: "Вопрос #{id} попадёт в категорию \"{category}\". Напишите ответ одним сообщением:".to_string(),
+                admin_promotion_cancelled: "Добавление вопроса #{id} в базу знаний отменено".to_string(),
+                admin_category_gone: "Категории \"{category}\" больше нет в базе знаний".to_string(),
+                admin_already_known: "В базе знаний уже есть \"{question}\"".to_string(),
+                admin_knowledge_failed: "Не удалось обновить базу знаний: {error}".to_string(),
+                admin_promoted: "Вопрос #{id} добавлен в базу знаний: {category} / {question}".to_string(),
+                admin_choose_original: "Выберите вопрос базы знаний, повтором которого является #{id}:\n{question}".to_string(),
+                admin_duplicate_cancelled: "Отметка вопроса #{id} как повтора отменена".to_string(),
+                admin_duplicate: "Вопрос #{id} отмечен как повтор \"{original}\", ответ отправляется автору".to_string(),
+                admin_similar: "Похож на вопрос базы знаний \"{question}\"".to_string(),
+                admin_no_similar: "Похожего вопроса в базе знаний больше нет".to_string(),
+                admin_broadcast: "Рассылка поставлена в очередь, чатов: {count}".to_string(),
+                report_title: "Статистика с {from} по {to}".to_string(),
+                report_daily_users: "Пользователей по дням:".to_string(),
+                report_messages: "Сообщений обработано: {count}".to_string(),
+                report_top_questions: "Популярные вопросы:".to_string(),
+                report_top_categories: "Популярные категории:".to_string(),
+                report_unmatched: "Частые непонятые запросы:".to_string(),
+                report_empty: " нет".to_string(),
+                report_pending: "На модерации: {count}".to_string(),
+                report_answer_time: "Среднее время ответа на сохранённый вопрос: {time}".to_string(),
+                report_no_answers: "ответов не было".to_string(),
+                report_seconds: "{seconds} сек".to_string(),
+                report_minutes: "{minutes} мин".to_string(),
+                report_hours: "{hours} ч {minutes} мин".to_string(),
+            },
+            "en" => Messages {
+                start: "Hi! I'm a bot!".to_string(),
+                help: "Pick a question or type your own".to_string(),
+                reset: "Back to the start.".to_string(),
+                unknown_command: "Unknown command".to_string(),
+                category: "Category: \"{category}\"".to_string(),
+                not_found: "Sorry, I don't know the answer to your question...".to_string(),
+                text_only: "Sorry, I only understand text questions!".to_string(),
+                choice_saved: "Got your choice, thanks!".to_string(),
+                button_yes: "Yes".to_string(),
+                button_no: "No".to_string(),
+                command_start: "Start".to_string(),
+                command_reset: "Back to the start".to_string(),
+                command_help: "Help".to_string(),
+                admin_only: "This command is only available to administrators".to_string(),
+                admin_answer: "Answer to your question \"{question}\":\n{answer}".to_string(),
+                button_operator: "Contact an operator".to_string(),
+                command_operator: "Contact an operator".to_string(),
+                operator_connected: "You are connected to an operator. Write here and the operator will reply.".to_string(),
+                operator_close: "End conversation".to_string(),
+                operator_closed: "The conversation with the operator has ended.".to_string(),
+                operator_reply: "Operator: {text}".to_string(),
+                operator_unavailable: "Operators are not available right now.".to_string(),
+                operator_ticket: "Ticket #{id} from {author}".to_string(),
+                operator_ticket_message: "#{id} {author}: {text}".to_string(),
+                button_close_ticket: "Close ticket".to_string(),
+                operator_bad_button: "Invalid button".to_string(),
+                operator_error: "Error: {error}".to_string(),
+                operator_not_found: "Ticket #{id} not found".to_string(),
+                operator_already_closed: "Ticket #{id} is already closed".to_string(),
+                operator_closed_by_user: "Ticket #{id} closed by the user".to_string(),
+                operator_closed_by_operator: "Ticket #{id} closed by an operator".to_string(),
+                feedback_prompt: "Did this answer help?".to_string(),
+                button_helpful: "👍".to_string(),
+                button_unhelpful: "👎".to_string(),
+                feedback_thanks: "Thanks for the feedback!".to_string(),
+                feedback_save: "Sorry about that! Save your question so that a person can answer it?".to_string(),
+                feedback_question: "The answer to \"{question}\" did not help".to_string(),
+                command_reload: "Reload the knowledge base".to_string(),
+                command_stats: "Bot statistics: /stats [days] [csv]".to_string(),
+                command_pending: "User questions awaiting moderation: /pending [page]".to_string(),
+                command_answer: "Answer a question: /answer <number> <answer>".to_string(),
+                command_broadcast: "Message all users: /broadcast <text>".to_string(),
+                command_promote: "Add a question to the knowledge base: /promote <number>".to_string(),
+                command_feedback: "Ratings of knowledge base answers".to_string(),
+                usage_stats: "Usage: /stats [days, up to {max}] [csv]".to_string(),
+                usage_pending: "Usage: /pending [page]".to_string(),
+                usage_answer: "Usage: /answer <number> <answer>".to_string(),
+                usage_broadcast: "Usage: /broadcast <text>".to_string(),
+                usage_promote: "Usage: /promote <number>".to_string(),
+                button_approve: "Approve".to_string(),
+                button_reject: "Reject".to_string(),
+                button_answer: "Answer".to_string(),
+                button_duplicate: "Duplicate".to_string(),
+                button_promote: "To knowledge base".to_string(),
+                button_similar: "Yes, a duplicate".to_string(),
+                button_here: "✅ Here".to_string(),
+                button_up: "⬆️ Back".to_string(),
+                button_cancel: "❌ Cancel".to_string(),
+                button_keep_answer: "Keep the previous answer".to_string(),
+                admin_bad_button: "Invalid button".to_string(),
+                admin_error: "Error: {error}".to_string(),
+                admin_not_found: "Question #{id} not found".to_string(),
+                admin_approved: "Question #{id} approved".to_string(),
+                admin_rejected: "Question #{id} rejected".to_string(),
+                admin_question: "#{id} from {author}, {date}{details}\n{question}".to_string(),
+                admin_question_approved: ", approved".to_string(),
+                admin_question_asked: ", asked {count} times".to_string(),
+                admin_question_asked_few: ", asked {count} times".to_string(),
+                admin_new_question: "New question {question}".to_string(),
+                admin_answer_prompt: "Write the answer to question #{id} in one message:\n{question}".to_string(),
+                admin_answer_cancelled: "The answer to question #{id} is cancelled".to_string(),
+                admin_expired: "Timed out, the action is cancelled. The message was not saved.".to_string(),
+                admin_answer_saved: "The answer to question #{id} is saved and being sent to the author".to_string(),
+                admin_answer_saved_repeats: "The answer to question #{id} is saved and being sent to the author and {others} more who asked".to_string(),
+                admin_answer_failed: "Could not save the answer to question #{id}: {error}".to_string(),
+                admin_question_failed: "Could not read question #{id}: {error}".to_string(),
+                admin_reload_failed: "Could not reload the knowledge base, keeping the old one: {error}".to_string(),
+                admin_reloaded: "Knowledge base reloaded, questions: {count}".to_string(),
+                admin_no_changes: "No changes.".to_string(),
+                admin_changes: "Changes: {count}".to_string(),
+                admin_problems: "Problems:".to_string(),
+                admin_stats: "Knowledge base questions: {questions}\nSaved user questions: {saved}\nChats: {chats}".to_string(),
+                admin_report_failed: "Could not build the report: {error}".to_string(),
+                admin_export_failed: "Could not export the report: {error}".to_string(),
+                admin_no_votes: "Nobody has rated the answers yet.".to_string(),
+                admin_votes: "Answer ratings, worst first:".to_string(),
+                admin_vote: "{score}% 👍 ({helpful} 👍, {unhelpful} 👎) {question}".to_string(),
+                admin_no_pending: "No questions awaiting moderation.".to_string(),
+                admin_pending: "Questions awaiting moderation: {total}, page {page} of {pages}".to_string(),
+                admin_next_page: "Next: /pending {page}".to_string(),
+                admin_choose_category: "Choose a category for question #{id}:\n{question}".to_string(),
+                admin_root: "Knowledge base root".to_string(),
+                admin_category: "Category \"{category}\"".to_string(),
+                admin_promoted_answer: "Question #{id} will go to category \"{category}\". Write the answer in one message:".to_string(),
+                admin_promotion_cancelled: "Adding question #{id} to the knowledge base cancelled".to_string(),
+                admin_category_gone: "Category \"{category}\" is no longer in the knowledge base".to_string(),
+                admin_already_known: "The knowledge base already has \"{question}\"".to_string(),
+                admin_knowledge_failed: "Could not update the knowledge base: {error}".to_string(),
+                admin_promoted: "Question #{id} added to the knowledge base: {category} / {question}".to_string(),
+                admin_choose_original: "Choose the knowledge base question that #{id} repeats:\n{question}".to_string(),
+                admin_duplicate_cancelled: "Marking question #{id} as a duplicate cancelled".to_string(),
+                admin_duplicate: "Question #{id} marked as a duplicate of \"{original}\", the answer is being sent to the author".to_string(),
+                admin_similar: "Looks like the knowledge base question \"{question}\"".to_string(),
+                admin_no_similar: "There is no similar knowledge base question anymore".to_string(),
+                admin_broadcast: "Broadcast queued, chats: {count}".to_string(),
+                report_title: "Statistics from {from} to {to}".to_string(),
+                report_daily_users: "Users per day:".to_string(),
+                report_messages: "Messages processed: {count}".to_string(),
+                report_top_questions: "Popular questions:".to_string(),
+                report_top_categories: "Popular categories:".to_string(),
+                report_unmatched: "Frequent unrecognised queries:".to_string(),
+                report_empty: " none".to_string(),
+                report_pending: "Awaiting moderation: {count}".to_string(),
+                report_answer_time: "Mean time to answer a saved question: {time}".to_string(),
+                report_no_answers: "no answers".to_string(),
+                report_seconds: "{seconds} s".to_string(),
+                report_minutes: "{minutes} min".to_string(),
+                report_hours: "{hours} h {minutes} min".to_string(),
+            },
+            _ => return None,
+        };
+
+        Some(texts)
+    }
+
+    /// Заменяет текст по имени ключа из файла конфигурации
+    fn set(&mut self, key: &str, value: String) -> bool {
+        let field = match key {
+            "start" => &mut self.start,
+            "help" => &mut self.help,
+            "reset" => &mut self.reset,
+            "unknown_command" => &mut self.unknown_command,
+            "category" => &mut self.category,
+            "not_found" => &mut self.not_found,
+            "text_only" => &mut self.text_only,
+            "choice_saved" => &mut self.choice_saved,
+            "button_yes" => &mut self.button_yes,
+            "button_no" => &mut self.button_no,
+            "command_start" => &mut self.command_start,
+            "command_reset" => &mut self.command_reset,
+            "command_help" => &mut self.command_help,
+            "admin_only" => &mut self.admin_only,
+            "admin_answer" => &mut self.admin_answer,
+            "button_operator" => &mut self.button_operator,
+            "command_operator" => &mut self.command_operator,
+            "operator_connected" => &mut self.operator_connected,
+            "operator_close" => &mut self.operator_close,
+            "operator_closed" => &mut self.operator_closed,
+            "operator_reply" => &mut self.operator_reply,
+            "operator_unavailable" => &mut self.operator_unavailable,
+            "operator_ticket" => &mut self.operator_ticket,
+            "operator_ticket_message" => &mut self.operator_ticket_message,
+            "button_close_ticket" => &mut self.button_close_ticket,
+            "operator_bad_button" => &mut self.operator_bad_button,
+            "operator_error" => &mut self.operator_error,
+            "operator_not_found" => &mut self.operator_not_found,
+            "operator_already_closed" => &mut self.operator_already_closed,
+            "operator_closed_by_user" => &mut self.operator_closed_by_user,
+            "operator_closed_by_operator" => &mut self.operator_closed_by_operator,
+            "feedback_prompt" => &mut self.feedback_prompt,
+            "button_helpful" => &mut self.button_helpful,
+            "button_unhelpful" => &mut self.button_unhelpful,
+            "feedback_thanks" => &mut self.feedback_thanks,
+            "feedback_save" => &mut self.feedback_save,
+            "feedback_question" => &mut self.feedback_question,
+            "command_reload" => &mut self.command_reload,
+            "command_stats" => &mut self.command_stats,
+            "command_pending" => &mut self.command_pending,
+            "command_answer" => &mut self.command_answer,
+            "command_broadcast" => &mut self.command_broadcast,
+            "command_promote" => &mut self.command_promote,
+            "command_feedback" => &mut self.command_feedback,
+            "usage_stats" => &mut self.usage_stats,
+            "usage_pending" => &mut self.usage_pending,
+            "usage_answer" => &mut self.usage_answer,
+            "usage_broadcast" => &mut self.usage_broadcast,
+            "usage_promote" => &mut self.usage_promote,
+            "button_approve" => &mut self.button_approve,
+            "button_reject" => &mut self.button_reject,
+            "button_answer" => &mut self.button_answer,
+            "button_duplicate" => &mut self.button_duplicate,
+            "button_promote" => &mut self.button_promote,
+            "button_similar" => &mut self.button_similar,
+            "button_here" => &mut self.button_here,
+            "button_up" => &mut self.button_up,
+            "button_cancel" => &mut self.button_cancel,
+            "button_keep_answer" => &mut self.button_keep_answer,
+            "admin_bad_button" => &mut self.admin_bad_button,
+            "admin_error" => &mut self.admin_error,
+            "admin_not_found" => &mut self.admin_not_found,
+            "admin_approved" => &mut self.admin_approved,
+            "admin_rejected" => &mut self.admin_rejected,
+            "admin_question" => &mut self.admin_question,
+            "admin_question_approved" => &mut self.admin_question_approved,
+            "admin_question_asked" => &mut self.admin_question_asked,
+            "admin_question_asked_few" => &mut self.admin_question_asked_few,
+            "admin_new_question" => &mut self.admin_new_question,
+            "admin_answer_prompt" => &mut self.admin_answer_prompt,
+            "admin_answer_cancelled" => &mut self.admin_answer_cancelled,
+            "admin_expired" => &mut self.admin_expired,
+            "admin_answer_saved" => &mut self.admin_answer_saved,
+            "admin_answer_saved_repeats" => &mut self.admin_answer_saved_repeats,
+            "admin_answer_failed" => &mut self.admin_answer_failed,
+            "admin_question_failed" => &mut self.admin_question_failed,
+            "admin_reload_failed" => &mut self.admin_reload_failed,
+            "admin_reloaded" => &mut self.admin_reloaded,
+            "admin_no_changes" => &mut self.admin_no_changes,
+            "admin_changes" => &mut self.admin_changes,
+            "admin_problems" => &mut self.admin_problems,
+            "admin_stats" => &mut self.admin_stats,
+            "admin_report_failed" => &mut self.admin_report_failed,
+            "admin_export_failed" => &mut self.admin_export_failed,
+            "admin_no_votes" => &mut self.admin_no_votes,
+            "admin_votes" => &mut self.admin_votes,
+            "admin_vote" => &mut self.admin_vote,
+            "admin_no_pending" => &mut self.admin_no_pending,
+            "admin_pending" => &mut self.admin_pending,
+            "admin_next_page" => &mut self.admin_next_page,
+            "admin_choose_category" => &mut self.admin_choose_category,
+            "admin_root" => &mut self.admin_root,
+            "admin_category" => &mut self.admin_category,
+            "admin_promoted_answer" => &mut self.admin_promoted_answer,
+            "admin_promotion_cancelled" => &mut self.admin_promotion_cancelled,
+            "admin_category_gone" => &mut self.admin_category_gone,
+            "admin_already_known" => &mut self.admin_already_known,
+            "admin_knowledge_failed" => &mut self.admin_knowledge_failed,
+            "admin_promoted" => &mut self.admin_promoted,
+            "admin_choose_original" => &mut self.admin_choose_original,
+            "admin_duplicate_cancelled" => &mut self.admin_duplicate_cancelled,
+            "admin_duplicate" => &mut self.admin_duplicate,
+            "admin_similar" => &mut self.admin_similar,
+            "admin_no_similar" => &mut self.admin_no_similar,
+            "admin_broadcast" => &mut self.admin_broadcast,
+            "report_title" => &mut self.report_title,
+            "report_daily_users" => &mut self.report_daily_users,
+            "report_messages" => &mut self.report_messages,
+            "report_top_questions" => &mut self.report_top_questions,
+            "report_top_categories" => &mut self.report_top_categories,
+            "report_unmatched" => &mut self.report_unmatched,
+            "report_empty" => &mut self.report_empty,
+            "report_pending" => &mut self.report_pending,
+            "report_answer_time" => &mut self.report_answer_time,
+            "report_no_answers" => &mut self.report_no_answers,
+            "report_seconds" => &mut self.report_seconds,
+            "report_minutes" => &mut self.report_minutes,
+            "report_hours" => &mut self.report_hours,
+            _ => return false,
+        };
+        *field = value;
+
+        true
+    }
+}
+
+/// Подставляет значения в текст: `{name}` заменяется на значение `name`.
+/// Подставленное больше не просматривается, так что скобки в тексте
+/// пользователя остаются как есть
+pub fn fill(template: &str, values: &[(&str, &dyn Display)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            values.iter()
+                  .find(|(name, _)| *name == &rest[1..end])
+                  .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                text.push_str(&value.to_string());
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+
+    text
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::for_language("ru").unwrap()
+    }
+}
+
+/// Конфигурация в том виде, в котором она записана в файле
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    token: String,
+    api_url: String,
+    admins: Vec<u64>,
+    admin_chat: Option<i64>,
+    admin_timeout: u32,
+    operator_chat: Option<i64>,
+    metrics_listen: Option<String>,
+    mode: Mode,
+    language: String,
+    paths: Paths,
+    polling: Polling,
+    webhook: Webhook,
+    /// Тексты, заменяющие тексты выбранного языка
+    messages: BTreeMap<String, String>,
+}
+
+impl Default for RawConfig {
+    fn default() -> Self {
+        RawConfig {
+            token: String::new(),
+            api_url: DEFAULT_API_URL.to_string(),
+            admins: vec![],
+            admin_chat: None,
+            admin_timeout: DEFAULT_ADMIN_TIMEOUT,
+            operator_chat: None,
+            metrics_listen: None,
+            mode: Mode::Polling,
+            language: "ru".to_string(),
+            paths: Paths::default(),
+            polling: Polling::default(),
+            webhook: Webhook::default(),
+            messages: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub token: String,
+    /// Адрес Bot API; для тестов можно указать локальный сервер
+    pub api_url: String,
+    /// Telegram id пользователей с правами администратора
+    pub admins: Vec<u64>,
+    /// Чат (или группа), куда приходят новые сохранённые вопросы.
+    /// Его участники могут отвечать на них, отклонять и отмечать повторы
+    pub admin_chat: Option<i64>,
+    /// Сколько секунд бот ждёт от администратора ответа или выбора категории
+    pub admin_timeout: u32,
+    /// Группа операторов, которым пересылаются разговоры пользователей
+    pub operator_chat: Option<i64>,
+    /// Адрес HTTP-сервера с метриками Prometheus и `/healthz`; без него сервер не запускается
+    pub metrics_listen: Option<String>,
+    pub mode: Mode,
+    pub language: String,
+    pub paths: Paths,
+    pub polling: Polling,
+    pub webhook: Webhook,
+    pub messages: Messages,
+}
+
+/// Файл конфигурации по умолчанию
+pub static DEFAULT_PATH: &str = "config.toml";
+
+pub static DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+/// HTTP-клиент frankenstein обрывает запрос через 500 секунд, поэтому
+/// getUpdates должен вернуться раньше, с запасом на сеть
+pub const MAX_POLL_TIMEOUT: u32 = 450;
+
+pub const DEFAULT_ADMIN_TIMEOUT: u32 = 600;
+
+impl Config {
+    /// Читает конфигурацию из файла (если он есть; по умолчанию путь берётся
+    /// из `BOT_CONFIG`), применяет переменные окружения `BOT_*` и проверяет,
+    /// что с ней можно запустить бота
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        let (config, mut problems) = Self::read(&Self::path(path), |name| std::env::var(name).ok())?;
+        config.validate(&mut problems);
+
+        Self::checked(config, problems)
+    }
+
+    /// Как `load`, но без проверок, нужных только для запуска бота
+    /// (токен, файл базы знаний) - для служебных команд
+    pub fn load_unchecked(path: Option<&str>) -> Result<Self, ConfigError> {
+        let (config, problems) = Self::read(&Self::path(path), |name| std::env::var(name).ok())?;
+
+        Self::checked(config, problems)
+    }
+
+    fn path(path: Option<&str>) -> String {
+        path.map(str::to_owned)
+            .or_else(|| std::env::var("BOT_CONFIG").ok())
+            .unwrap_or_else(|| DEFAULT_PATH.to_string())
+    }
+
+    fn checked(config: Self, problems: Vec<String>) -> Result<Self, ConfigError> {
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+
+    fn read(path: &str, env: impl Fn(&str) -> Option<String>) -> Result<(Self, Vec<String>), ConfigError> {
+        let mut raw = if Path::new(path).exists() {
+            let text = std::fs::read_to_string(path)
+                                 .map_err(|error| ConfigError::single(format!("{path}: {error}")))?;
+            toml::from_str::<RawConfig>(&text)
+                 .map_err(|error| ConfigError::single(format!("{path}: {error}")))?
+        } else {
+            RawConfig::default()
+        };
+
+        let mut problems = vec![];
+        apply_env(&mut raw, env, &mut problems);
+
+        let mut messages = Messages::for_language(&raw.language).unwrap_or_else(|| {
+            problems.push(format!("неизвестный язык \"{}\" (доступны: ru, en)", raw.language));
+            Messages::default()
+        });
+        for (key, value) in raw.messages {
+            if !messages.set(&key, value) {
+                problems.push(format!("неизвестный текст messages.{key}"));
+            }
+        }
+
+        let config = Config {
+            token: raw.token,
+            api_url: raw.api_url,
+            admins: raw.admins,
+            admin_chat: raw.admin_chat,
+            admin_timeout: raw.admin_timeout,
+            operator_chat: raw.operator_chat,
+            metrics_listen: raw.metrics_listen,
+            mode: raw.mode,
+            language: raw.language,
+            paths: raw.paths,
+            polling: raw.polling,
+            webhook: raw.webhook,
+            messages,
+        };
+
+        Ok((config, problems))
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.token.is_empty() {
+            problems.push("не задан токен бота (token или BOT_TOKEN)".to_string());
+        }
+        if !self.api_url.starts_with("http://") && !self.api_url.starts_with("https://") {
+            problems.push(format!("неверный api_url: {}", self.api_url));
+        }
+        if !Path::new(&self.paths.knowledge_base).is_file() {
+            problems.push(format!("не найден файл базы знаний {}", self.paths.knowledge_base));
+        }
+        if self.mode == Mode::Webhook {
+            if self.webhook.url.is_empty() {
+                problems.push("для режима webhook нужен webhook.url".to_string());
+            }
+            if self.webhook.listen.parse::<std::net::SocketAddr>().is_err() {
+                problems.push(format!("неверный адрес webhook.listen: {}", self.webhook.listen));
+            }
+            // Telegram принимает секрет из 1-256 символов A-Z, a-z, 0-9, _ и -
+            let secret = &self.webhook.secret;
+            if secret.is_empty() {
+                problems.push("для режима webhook нужен webhook.secret".to_string());
+            } else if secret.len() > 256 || !secret.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                problems.push("неверный webhook.secret: допустимы 1-256 символов A-Z, a-z, 0-9, _ и -".to_string());
+            }
+        }
+        if let Some(listen) = &self.metrics_listen {
+            if listen.parse::<std::net::SocketAddr>().is_err() {
+                problems.push(format!("неверный адрес metrics_listen: {listen}"));
+            }
+        }
+        if self.polling.timeout > MAX_POLL_TIMEOUT {
+            problems.push(format!("polling.timeout не может быть больше {MAX_POLL_TIMEOUT} секунд"));
+        }
+        // В чате администраторов бот не отвечает на переписку, и ответы операторов бы терялись
+        if self.admin_chat.is_some() && self.admin_chat == self.operator_chat {
+            problems.push("admin_chat и operator_chat должны быть разными чатами".to_string());
+        }
+        if self.admin_timeout == 0 {
+            problems.push("admin_timeout должен быть больше нуля".to_string());
+        }
+    }
+
+    pub fn polling_config(&self) -> PollingConfig {
+        PollingConfig { timeout: self.polling.timeout, ..Default::default() }
+    }
+
+    /// Адрес, к которому добавляются имена методов Bot API
+    pub fn bot_api_url(&self) -> String {
+        format!("{}/bot{}", self.api_url.trim_end_matches('/'), self.token)
+    }
+
+    pub fn is_admin(&self, user_id: u64) -> bool {
+        self.admins.contains(&user_id)
+    }
+}
+
+/// Переменные окружения важнее файла конфигурации
+fn apply_env(raw: &mut RawConfig, env: impl Fn(&str) -> Option<String>, problems: &mut Vec<String>) {
+    if let Some(token) = env("BOT_TOKEN") {
+        raw.token = token;
+    }
+    if let Some(url) = env("BOT_API_URL") {
+        raw.api_url = url;
+    }
+    if let Some(path) = env("BOT_QUESTIONS_DB") {
+        raw.paths.questions_db = path;
+    }
+    if let Some(path) = env("BOT_KNOWLEDGE_BASE") {
+        raw.paths.knowledge_base = path;
+    }
+    if let Some(path) = env("BOT_SNAPSHOT") {
+        raw.paths.snapshot = path;
+    }
+    if let Some(language) = env("BOT_LANGUAGE") {
+        raw.language = language;
+    }
+    if let Some(url) = env("BOT_WEBHOOK_URL") {
+        raw.webhook.url = url;
+    }
+    if let Some(listen) = env("BOT_WEBHOOK_LISTEN") {
+        raw.webhook.listen = listen;
+    }
+    if let Some(secret) = env("BOT_WEBHOOK_SECRET") {
+        raw.webhook.secret = secret;
+    }
+    if let Some(listen) = env("BOT_METRICS_LISTEN") {
+        raw.metrics_listen = Some(listen);
+    }
+    if let Some(mode) = env("BOT_MODE") {
+        match mode.as_str() {
+            "polling" => raw.mode = Mode::Polling,
+            "async"   => raw.mode = Mode::Async,
+            "webhook" => raw.mode = Mode::Webhook,
+            _         => problems.push(format!("неверный BOT_MODE: {mode} (polling, async или webhook)")),
+        }
+    }
+    if let Some(timeout) = env("BOT_POLL_TIMEOUT") {
+        match timeout.parse() {
+            Ok(timeout) => raw.polling.timeout = timeout,
+            Err(_) => problems.push(format!("неверный BOT_POLL_TIMEOUT: {timeout}")),
+        }
+    }
+    if let Some(chat) = env("BOT_ADMIN_CHAT") {
+        match chat.trim().parse() {
+            Ok(chat) => raw.admin_chat = Some(chat),
+            Err(_) => problems.push(format!("неверный BOT_ADMIN_CHAT: {chat}")),
+        }
+    }
+    if let Some(timeout) = env("BOT_ADMIN_TIMEOUT") {
+        match timeout.parse() {
+            Ok(timeout) => raw.admin_timeout = timeout,
+            Err(_) => problems.push(format!("неверный BOT_ADMIN_TIMEOUT: {timeout}")),
+        }
+    }
+    if let Some(chat) = env("BOT_OPERATOR_CHAT") {
+        match chat.trim().parse() {
+            Ok(chat) => raw.operator_chat = Some(chat),
+            Err(_) => problems.push(format!("неверный BOT_OPERATOR_CHAT: {chat}")),
+        }
+    }
+    if let Some(admins) = env("BOT_ADMINS") {
+        let parsed: Result<Vec<u64>, _> = admins.split(',')
+                                                .map(str::trim)
+                                                .filter(|id| !id.is_empty())
+                                                .map(str::parse)
+                                                .collect();
+        match parsed {
+            Ok(ids) => raw.admins = ids,
+            Err(_) => problems.push(format!("неверный BOT_ADMINS: {admins} (ожидаются id через запятую)")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Конфигурация из переменных окружения `vars` без файла
+    fn read(vars: &[(&str, &str)]) -> (Config, Vec<String>) {
+        let env = |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string());
+        Config::read("не существует.toml", env).unwrap()
+    }
+
+    fn validate(vars: &[(&str, &str)]) -> Vec<String> {
+        let (config, mut problems) = read(vars);
+        config.validate(&mut problems);
+        problems
+    }
+
+    const WEBHOOK: [(&str, &str); 4] = [
+        ("BOT_TOKEN", "token"),
+        ("BOT_KNOWLEDGE_BASE", "tests/data/qna.json"),
+        ("BOT_MODE", "webhook"),
+        ("BOT_WEBHOOK_URL", "https://example.com/hook"),
+    ];
+
+    #[test]
+    fn env_overrides_defaults() {
+        let (config, problems) = read(&[
+            ("BOT_TOKEN", "token"),
+            ("BOT_API_URL", "http://localhost:8081"),
+            ("BOT_ADMINS", " 1, 2 ,"),
+            ("BOT_ADMIN_CHAT", "-100"),
+            ("BOT_ADMIN_TIMEOUT", "60"),
+            ("BOT_OPERATOR_CHAT", " -200 "),
+            ("BOT_MODE", "async"),
+            ("BOT_LANGUAGE", "en"),
+            ("BOT_POLL_TIMEOUT", "10"),
+            ("BOT_QUESTIONS_DB", "q.db"),
+            ("BOT_METRICS_LISTEN", "127.0.0.1:9100"),
+        ]);
+
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(config.token, "token");
+        assert_eq!(config.bot_api_url(), "http://localhost:8081/bottoken");
+        assert_eq!(config.admins, [1, 2]);
+        assert_eq!((config.admin_chat, config.operator_chat), (Some(-100), Some(-200)));
+        assert_eq!(config.admin_timeout, 60);
+        assert_eq!(config.mode, Mode::Async);
+        assert_eq!(config.messages.start, "Hi! I'm a bot!");
+        assert_eq!(config.polling.timeout, 10);
+        assert_eq!(config.paths.questions_db, "q.db");
+        assert_eq!(config.paths.knowledge_base, "db.json");
+        assert_eq!(config.metrics_listen.as_deref(), Some("127.0.0.1:9100"));
+    }
+
+    #[test]
+    fn env_reports_invalid_values() {
+        let (config, problems) = read(&[
+            ("BOT_ADMINS", "1,два"),
+            ("BOT_ADMIN_CHAT", "чат"),
+            ("BOT_MODE", "push"),
+            ("BOT_POLL_TIMEOUT", "-1"),
+            ("BOT_LANGUAGE", "de"),
+        ]);
+
+        assert_eq!(problems, [
+            "неверный BOT_MODE: push (polling, async или webhook)",
+            "неверный BOT_POLL_TIMEOUT: -1",
+            "неверный BOT_ADMIN_CHAT: чат",
+            "неверный BOT_ADMINS: 1,два (ожидаются id через запятую)",
+            "неизвестный язык \"de\" (доступны: ru, en)",
+        ]);
+        // Неверные значения не заменяют значения по умолчанию
+        assert_eq!(config.mode, Mode::Polling);
+        assert_eq!(config.polling.timeout, 30);
+        assert!(config.admins.is_empty());
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        assert_eq!(validate(&[
+            ("BOT_API_URL", "localhost"),
+            ("BOT_KNOWLEDGE_BASE", "нет такого файла.json"),
+            ("BOT_METRICS_LISTEN", "localhost"),
+            ("BOT_POLL_TIMEOUT", "451"),
+            ("BOT_ADMIN_TIMEOUT", "0"),
+        ]), [
+            "не задан токен бота (token или BOT_TOKEN)",
+            "неверный api_url: localhost",
+            "не найден файл базы знаний нет такого файла.json",
+            "неверный адрес metrics_listen: localhost",
+            "polling.timeout не может быть больше 450 секунд",
+            "admin_timeout должен быть больше нуля",
+        ]);
+    }
+
+    #[test]
+    fn validate_rejects_shared_admin_and_operator_chat() {
+        let vars = [("BOT_TOKEN", "token"), ("BOT_KNOWLEDGE_BASE", "tests/data/qna.json"),
+                    ("BOT_ADMIN_CHAT", "-100"), ("BOT_OPERATOR_CHAT", "-100")];
+
+        assert_eq!(validate(&vars), ["admin_chat и operator_chat должны быть разными чатами"]);
+    }
+
+    #[test]
+    fn validate_accepts_longest_poll_timeout() {
+        let vars = [("BOT_TOKEN", "token"), ("BOT_KNOWLEDGE_BASE", "tests/data/qna.json"), ("BOT_POLL_TIMEOUT", "450")];
+
+        assert!(validate(&vars).is_empty());
+    }
+
+    #[test]
+    fn webhook_requires_secret() {
+        assert_eq!(validate(&WEBHOOK), ["для режима webhook нужен webhook.secret"]);
+
+        let mut vars = WEBHOOK.to_vec();
+        vars.push(("BOT_WEBHOOK_SECRET", "не секрет"));
+        assert_eq!(validate(&vars), ["неверный webhook.secret: допустимы 1-256 символов A-Z, a-z, 0-9, _ и -"]);
+
+        vars.pop();
+        vars.push(("BOT_WEBHOOK_SECRET", "s3cret_-"));
+        assert!(validate(&vars).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+
+use crate::conversation::{ButtonPress, Conversation, Event, InlineButton, Keyboard, Reply, TextMessage};
+use crate::transport::Transport;
+use crate::util::logging::{check_result, non_fatal};
+
+// Консоль изображает один личный чат
+const CHAT_ID: i64 = 1;
+const USER_ID: u64 = 1;
+
+/// Что пользователь видит на экране
+#[derive(Default)]
+struct Screen {
+    /// Номер последнего сообщения бота
+    message_id: i32,
+    /// Номер нажатия кнопки
+    press_id: u32,
+    /// Текущая клавиатура пользователя
+    choices: Vec<String>,
+    /// Кнопки под последним сообщением бота
+    inline: Vec<(i32, InlineButton)>,
+}
+
+/// Разговор с ботом в терминале, без Telegram. Кнопки печатаются
+/// пронумерованными, ввод номера нажимает кнопку
+pub struct Console<'a, W: Write> {
+    conversation: &'a Conversation,
+    screen: RefCell<Screen>,
+    out: RefCell<W>,
+}
+
+impl<'a, W: Write> Console<'a, W> {
+    pub fn new(conversation: &'a Conversation, out: W) -> Self {
+        Console { conversation, screen: RefCell::default(), out: RefCell::new(out) }
+    }
+
+    pub fn run(&self, input: impl BufRead) -> io::Result<()> {
+        writeln!(self.out.borrow_mut(), "Пишите вопросы и команды или номер кнопки. Выход - :q или Ctrl+D")?;
+        self.dispatch("/start".to_string());
+
+        for line in input.lines() {
+            let line = line?;
+            match line.trim() {
+                "" => continue,
+                ":q" => break,
+                line => self.dispatch(line.to_string()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn show(&self, reply: Reply) -> io::Result<()> {
+        let mut out = self.out.borrow_mut();
+        let mut screen = self.screen.borrow_mut();
+
+        match reply {
+            Reply::Message { text, keyboard, .. } => {
+                screen.message_id += 1;
+                writeln!(out, "бот: {text}")?;
+
+                // Кнопки под прошлым сообщением в Telegram остаются, но нажимать их уже незачем
+                screen.inline.clear();
+                match keyboard {
+                    Some(Keyboard::Choices(choices)) => screen.choices = choices,
+                    Some(Keyboard::Inline(buttons)) => {
+                        let message_id = screen.message_id;
+                        screen.inline = buttons.into_iter().map(|button| (message_id, button)).collect();
+                    }
+                    None => (),
+                }
+            }
+            Reply::Notify { text, .. } if !text.is_empty() => writeln!(out, "[уведомление] {text}")?,
+            Reply::Notify { .. } => (),
+            Reply::RemoveButtons { message_id, .. } => screen.inline.retain(|(id, _)| *id != message_id),
+            // В консоли один чат, ответ на чужой вопрос просто показываем
+            Reply::Answer { question_id, chat_id, text } => {
+                writeln!(out, "бот -> чат {chat_id}: {text}")?;
+                check_result(self.conversation.question_db().record_delivery(question_id, Ok(())), non_fatal);
+            }
+            Reply::Forward { chat_id, text, .. } | Reply::Relay { chat_id, text, .. } => writeln!(out, "бот -> чат {chat_id}: {text}")?,
+            Reply::Document { file_name, content, .. } => writeln!(out, "бот: файл {file_name}\n{}", String::from_utf8_lossy(&content))?,
+        }
+
+        Ok(())
+    }
+
+    fn print_keyboard(&self) -> io::Result<()> {
+        let mut out = self.out.borrow_mut();
+        let screen = self.screen.borrow();
+
+        let inline = screen.inline.iter().map(|(_, button)| format!("{} (под сообщением)", button.text));
+        let buttons = inline.chain(screen.choices.iter().cloned());
+        for (number, text) in buttons.enumerate() {
+            writeln!(out, "  {}. {text}", number + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Transport for Console<'_, W> {
+    /// Строка, введённая пользователем
+    type Update = String;
+
+    fn conversation(&self) -> &Conversation {
+        self.conversation
+    }
+
+    fn event(&self, line: String) -> Option<Event> {
+        let mut screen = self.screen.borrow_mut();
+        let index = line.parse::<usize>().ok().and_then(|number| number.checked_sub(1));
+
+        // Номер кнопки под сообщением - нажатие
+        if let Some((message_id, button)) = index.and_then(|index| screen.inline.get(index)).cloned() {
+            screen.press_id += 1;
+            return Some(Event::Button(ButtonPress {
+                id: screen.press_id.to_string(),
+                chat_id: CHAT_ID,
+                user_id: USER_ID,
+                message_id: Some(message_id),
+                data: button.data,
+            }));
+        }
+
+        // Номер варианта с клавиатуры - его текст
+        let text = index.and_then(|index| index.checked_sub(screen.inline.len()))
+                        .and_then(|index| screen.choices.get(index))
+                        .cloned()
+                        .unwrap_or(line);
+
+        Some(Event::Text(TextMessage {
+            chat_id: CHAT_ID,
+            user_id: USER_ID,
+            username: None,
+            is_command: text.starts_with('/'),
+            text,
+            reply_to: None,
+        }))
+    }
+
+    fn deliver(&self, replies: Vec<Reply>) {
+        for reply in replies {
+            check_result(self.show(reply), non_fatal);
+        }
+        check_result(self.print_keyboard(), non_fatal);
+    }
+}
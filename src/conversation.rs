@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::admin::Admin;
+use crate::config::Messages;
+use crate::db;
+use crate::error::serde::IndexError;
+use crate::feedback;
+use crate::knowledge::KnowledgeBase;
+use crate::metrics::METRICS;
+use crate::operator::{Operators, OPERATOR_BUTTON};
+use crate::serde::QASerde;
+use crate::util::logging::{check_result, non_fatal};
+
+/// Текстовое сообщение пользователя
+#[derive(Debug, Clone)]
+pub struct TextMessage {
+    pub chat_id: i64,
+    pub user_id: u64,
+    /// Имя пользователя в мессенджере, если оно есть
+    pub username: Option<String>,
+    pub text: String,
+    /// Мессенджер пометил сообщение как команду боту
+    pub is_command: bool,
+    /// Сообщение бота, на которое отвечает пользователь
+    pub reply_to: Option<i32>,
+}
+
+/// Нажатие кнопки под сообщением
+#[derive(Debug, Clone)]
+pub struct ButtonPress {
+    /// Идентификатор нажатия, на которое отвечает `Reply::Notify`
+    pub id: String,
+    pub chat_id: i64,
+    pub user_id: u64,
+    /// Сообщение с кнопкой, если мессенджер его сообщил
+    pub message_id: Option<i32>,
+    pub data: String,
+}
+
+/// Событие от пользователя, не зависящее от мессенджера
+#[derive(Debug, Clone)]
+pub enum Event {
+    Text(TextMessage),
+    Button(ButtonPress),
+    /// Сообщение без текста: фото, стикер и т.п.
+    Unsupported { chat_id: i64 },
+}
+
+/// Кнопка под сообщением
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineButton {
+    pub text: String,
+    /// Что придёт в `ButtonPress::data` при нажатии
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Keyboard {
+    /// Варианты, которые пользователь отправляет одним нажатием
+    Choices(Vec<String>),
+    /// Кнопки под сообщением
+    Inline(Vec<InlineButton>),
+}
+
+/// Ответ бота, который мессенджер должен доставить
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// Сообщение в чат. Без клавиатуры у пользователя остаётся предыдущая
+    Message { chat_id: i64, text: String, keyboard: Option<Keyboard> },
+    /// Короткое уведомление в ответ на нажатие кнопки
+    Notify { press_id: String, text: String },
+    /// Убрать кнопки из сообщения
+    RemoveButtons { chat_id: i64, message_id: i32 },
+    /// Ответ администратора автору сохранённого вопроса. Мессенджер
+    /// записывает, удалось ли его доставить (`Database::record_delivery`)
+    Answer { question_id: i64, chat_id: i64, text: String },
+    /// Сохранённый вопрос в чате администраторов. Мессенджер запоминает
+    /// отправленное сообщение (`Database::remember_forward`), чтобы ответ
+    /// на него стал ответом на вопрос
+    Forward { question_id: i64, chat_id: i64, text: String, keyboard: Option<Keyboard> },
+    /// Сообщение в чат операторов. Мессенджер запоминает его
+    /// (`Database::remember_relay`), чтобы ответ оператора нашёл обращение
+    Relay { ticket_id: i64, chat_id: i64, text: String, keyboard: Option<Keyboard> },
+    /// Файл, например выгрузка отчёта
+    Document { chat_id: i64, file_name: String, content: Vec<u8> },
+}
+
+/// Вопрос, который сохранится, если пользователь согласится
+struct Offer {
+    message: TextMessage,
+    /// Предложено после 👎 ответу базы знаний на этот вопрос
+    unhelpful: bool,
+}
+
+/// Логика разговора с пользователем: по событию решает, что ответить.
+/// О мессенджерах ничего не знает
+pub struct Conversation {
+    knowledge: KnowledgeBase,
+    messages: Messages,
+    question_db: db::Database,
+    admin: Admin,
+    operators: Operators,
+    /// Последнее сообщение каждого чата - его сохраняем, если пользователь согласится
+    prev_messages: Mutex<HashMap<i64, Offer>>,
+}
+
+impl Conversation {
+    pub fn new(knowledge: KnowledgeBase, messages: Messages, question_db: db::Database) -> Self {
+        Conversation { knowledge, messages, question_db, admin: Admin::default(), operators: Operators::default(), prev_messages: Mutex::new(HashMap::new()) }
+    }
+
+    /// Включает команды администраторов
+    pub fn with_admin(mut self, admin: Admin) -> Self {
+        self.admin = admin;
+        self
+    }
+
+    /// Включает разговоры с операторами
+    pub fn with_operators(mut self, operators: Operators) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    pub fn knowledge(&self) -> &KnowledgeBase {
+        &self.knowledge
+    }
+
+    pub fn messages(&self) -> &Messages {
+        &self.messages
+    }
+
+    pub fn question_db(&self) -> &db::Database {
+        &self.question_db
+    }
+
+    pub fn admin(&self) -> &Admin {
+        &self.admin
+    }
+
+    pub fn operators(&self) -> &Operators {
+        &self.operators
+    }
+
+    /// Запоминает вопрос базы знаний, ответ на который не помог: он
+    /// сохранится, если пользователь нажмёт "Да"
+    pub fn offer_save_unhelpful(&self, chat_id: i64, user_id: u64, text: String) {
+        let mut prev_messages = self.prev_messages.lock().unwrap();
+        let username = prev_messages.get(&chat_id).and_then(|offer| offer.message.username.clone());
+        let message = TextMessage { chat_id, user_id, username, text, is_command: false, reply_to: None };
+        prev_messages.insert(chat_id, Offer { message, unhelpful: true });
+    }
+
+    /// Обрабатывает событие и возвращает ответы, которые нужно доставить
+    /// по порядку. Принимает `&self`, поэтому события разных чатов можно
+    /// обрабатывать параллельно
+    pub fn handle(&self, event: Event) -> Vec<Reply> {
+        match event {
+            Event::Text(message) => {
+                self.prev_messages.lock().unwrap().insert(message.chat_id, Offer { message: message.clone(), unhelpful: false });
+                check_result(self.question_db.remember_chat(message.chat_id), non_fatal);
+
+                if let Some(replies) = self.admin.handle(self, &message) {
+                    return replies;
+                }
+                if let Some(replies) = self.operators.handle(self, &message) {
+                    return replies;
+                }
+                self.process_message(message)
+            }
+            Event::Button(press) => self.process_button(press),
+            // Пользователь зачем-то отправил что-то
+            // другое вместо текстового сообщения
+            Event::Unsupported { chat_id } => vec![Self::message(chat_id, self.messages.text_only.clone(), None)],
+        }
+    }
+
+    fn process_button(&self, press: ButtonPress) -> Vec<Reply> {
+        if let Some(replies) = self.admin.handle_button(self, &press) {
+            return replies;
+        }
+        if let Some(replies) = self.operators.handle_button(self, &press) {
+            return replies;
+        }
+        if let Some(replies) = feedback::handle_button(self, &press) {
+            return replies;
+        }
+
+        // Пользователь зовёт оператора с вопросом, на который бот не ответил
+        if press.data == OPERATOR_BUTTON {
+            let offer = self.prev_messages.lock().unwrap().remove(&press.chat_id);
+            let question = offer.as_ref().map(|offer| match offer.unhelpful {
+                true => self.messages.feedback_question.replace("{question}", &offer.message.text),
+                false => offer.message.text.clone(),
+            });
+            let mut replies = vec![Reply::Notify { press_id: press.id, text: String::new() }];
+            replies.extend(press.message_id.map(|message_id| Reply::RemoveButtons { chat_id: press.chat_id, message_id }));
+            replies.extend(self.operators.open(self,
+                                               press.chat_id,
+                                               press.user_id,
+                                               offer.as_ref().and_then(|offer| offer.message.username.as_deref()),
+                                               question.as_deref()));
+            return replies;
+        }
+
+        // Сохраняем вопрос, если пользователь хочет
+        let mut forward = None;
+        if press.data == "save" {
+            if let Some(offer) = self.prev_messages.lock().unwrap().remove(&press.chat_id) {
+                self.record_event(db::EventKind::SaveAccepted, &offer.message);
+                forward = self.save_question(offer);
+            }
+        } else if press.data == "nosave" {
+            if let Some(offer) = self.prev_messages.lock().unwrap().get(&press.chat_id) {
+                self.record_event(db::EventKind::SaveDeclined, &offer.message);
+            }
+        }
+
+        // Ответ пользователю, что мы всё обработали
+        let mut replies = vec![Reply::Notify { press_id: press.id, text: self.messages.choice_saved.clone() }];
+        replies.extend(forward);
+
+        // Убираем кнопки с предыдущего сообщения
+        if let Some(message_id) = press.message_id {
+            replies.push(Reply::RemoveButtons { chat_id: press.chat_id, message_id });
+        }
+
+        replies
+    }
+
+    fn process_message(&self, message: TextMessage) -> Vec<Reply> {
+        // Берём текущую версию базы: перезагрузка не повлияет на этот ответ
+        let qna = self.knowledge.load();
+        let chat_id = message.chat_id;
+
+        // Проверяем, если пользователь отправил команду, и отправляем
+        // соответствующий ответ, если да
+        if message.is_command {
+            self.record_event(db::EventKind::Command, &message);
+            let (text, keyboard) = self.match_command(&qna, &message.text);
+            return vec![Self::message(chat_id, text, keyboard)];
+        }
+
+        // Не команда. Значит, вопрос?
+        let question = &message.text;
+        let (answer, keyboard) = Self::query_question(&qna, question.clone());
+        if let Some(answer) = answer {
+            self.record_event(db::EventKind::QuestionAnswered, &message);
+            vec![Self::message(chat_id, answer, keyboard), feedback::prompt(self, chat_id, question)]
+        } else if qna.contains(question) {
+            // Ответ не найден, но есть такая категория
+            self.record_event(db::EventKind::CategoryOpened, &message);
+            vec![Self::message(chat_id, self.messages.category.replace("{category}", question), keyboard)]
+        } else {
+            // Пользователь ввёл что-то невнятное. Предложим записать вопрос
+            self.record_event(db::EventKind::UnknownQuery, &message);
+            vec![Self::message(chat_id, self.messages.not_found.clone(), Some(self.save_keyboard()))]
+        }
+    }
+
+    /// Записывает действие пользователя для аналитики
+    fn record_event(&self, kind: db::EventKind, message: &TextMessage) {
+        METRICS.event(kind);
+        check_result(self.question_db.record_event(kind, message.chat_id, message.user_id, Some(&message.text)), non_fatal);
+    }
+
+    fn build_choice_keyboard(qna: &QASerde, parent: Option<String>) -> Result<Keyboard, IndexError<String>> {
+        eprintln!("Строим клавиатуру с родителем {parent:?}");
+        let children = qna.get_children(parent)?;
+        eprintln!("Элементы клавиатуры: {children:?}");
+
+        Ok(Keyboard::Choices(children))
+    }
+
+    /// Кнопки "сохранить вопрос?" и, если есть операторы, "связаться с оператором"
+    pub fn save_keyboard(&self) -> Keyboard {
+        let mut buttons = vec![
+            InlineButton { text: self.messages.button_yes.clone(), data: "save".to_string() },
+            InlineButton { text: self.messages.button_no.clone(), data: "nosave".to_string() },
+        ];
+        if self.operators.is_enabled() {
+            buttons.push(InlineButton { text: self.messages.button_operator.clone(), data: OPERATOR_BUTTON.to_string() });
+        }
+
+        Keyboard::Inline(buttons)
+    }
+
+    fn reset_choice_keyboard(qna: &QASerde) -> Option<Keyboard> {
+        eprintln!("Клавиатура сброшена");
+        Self::build_choice_keyboard(qna, None)
+            .map_err(non_fatal)
+            .ok()
+    }
+
+    /// Ответ на команду и клавиатура, которую нужно показать вместе с ним
+    fn match_command(&self, qna: &QASerde, msg: &str) -> (String, Option<Keyboard>) {
+        let (text, keyboard) = match msg {
+            "/start" => (&self.messages.start, Self::reset_choice_keyboard(qna)),
+            "/help"  => (&self.messages.help, None),
+            "/reset" => (&self.messages.reset, Self::reset_choice_keyboard(qna)),
+            _        => (&self.messages.unknown_command, None),
+        };
+
+        (text.to_string(), keyboard)
+    }
+
+    fn message(chat_id: i64, text: String, keyboard: Option<Keyboard>) -> Reply {
+        Reply::Message { chat_id, text, keyboard }
+    }
+
+    /// Ищет ответ на вопрос и клавиатуру, которую нужно показать пользователю
+    fn query_question(qna: &QASerde, question: String) -> (Option<String>, Option<Keyboard>) {
+        // Вопрос найден, отдаём ответ
+        if let Some(answer) = qna.get_answer(&question) {
+            return (Some(answer.to_owned()), Self::reset_choice_keyboard(qna));
+        }
+
+        if qna.contains(&question) {
+            // Это категория, идём глубже
+            let keyboard = Self::build_choice_keyboard(qna, Some(question))
+                               .map_err(non_fatal)
+                               .ok();
+            (None, keyboard)
+        } else {
+            // Не знаем такого вопроса/категории
+            (None, Self::reset_choice_keyboard(qna))
+        }
+    }
+
+    /// Сохраняет вопрос и возвращает уведомление для администраторов, если им есть куда его слать
+    fn save_question(&self, Offer { message, unhelpful }: Offer) -> Option<Reply> {
+        // Сохраняем вопрос
+        eprintln!("Cохраняем вопрос: {message:?}");
+        let saved = self.question_db.insert_question(&message.text, message.user_id, message.chat_id, message.username.as_deref(), unhelpful);
+        let id = saved.map_err(non_fatal).ok()?;
+
+        self.admin.forward_question(self, id)
+    }
+}
+
+// Обработчики событий делят ядро между потоками
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Conversation>();
+};
@@ -1,42 +1,857 @@
 use std::error::Error;
 
-use rusqlite::Connection;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Row, ToSql};
 use rusqlite::params;
+use serde::{Deserialize, Serialize};
 
+use crate::similarity;
+
+/// Миграции схемы по порядку. Номер версии схемы (`PRAGMA user_version`) -
+/// это число применённых миграций, поэтому менять уже выпущенные нельзя,
+/// только дописывать новые в конец
+const MIGRATIONS: &[&str] = &[
+    "create table if not exists questions (
+        id integer primary key,
+        user_id integer not null,
+        question text not null
+    )",
+    "create table if not exists chats (
+        id integer primary key,
+        first_seen text not null default current_timestamp
+    )",
+    // Очередь модерации. Старые вопросы задавали в личных чатах,
+    // поэтому их чат совпадает с пользователем
+    "create table questions_v3 (
+        id integer primary key,
+        user_id integer not null,
+        chat_id integer not null,
+        username text,
+        question text not null,
+        status text not null default 'new',
+        answer text,
+        created_at text not null default current_timestamp,
+        updated_at text not null default current_timestamp
+    );
+    insert into questions_v3 (id, user_id, chat_id, question)
+        select id, user_id, user_id, question from questions;
+    drop table questions;
+    alter table questions_v3 rename to questions;
+    create index questions_status on questions (status, id);",
+    // Доставка ответа администратора автору вопроса
+    "alter table questions add column delivery text;
+    alter table questions add column delivery_error text;
+    alter table questions add column delivered_at text;",
+    // Вопросы, пересланные в чат администраторов: ответ на такое сообщение - ответ на вопрос
+    "create table forwarded (
+        chat_id integer not null,
+        message_id integer not null,
+        question_id integer not null,
+        primary key (chat_id, message_id)
+    );",
+    // Разговоры с оператором и сообщения в чате операторов, ответ на которые уходит пользователю
+    "create table tickets (
+        id integer primary key,
+        chat_id integer not null,
+        user_id integer not null,
+        username text,
+        created_at text not null default current_timestamp,
+        closed_at text
+    );
+    create index tickets_chat on tickets (chat_id, closed_at);
+    create table relayed (
+        chat_id integer not null,
+        message_id integer not null,
+        ticket_id integer not null,
+        primary key (chat_id, message_id)
+    );",
+    // Повторы вопроса: у повтора cluster_id - первый такой вопрос, а у первого
+    // asked - сколько раз его задали
+    "alter table questions add column cluster_id integer;
+    alter table questions add column asked integer not null default 1;",
+    // Действия пользователей для аналитики: что открывают, о чём спрашивают
+    "create table events (
+        id integer primary key,
+        kind text not null,
+        chat_id integer not null,
+        user_id integer not null,
+        label text,
+        created_at text not null default current_timestamp
+    );
+    create index events_kind on events (kind, created_at);",
+    // Оценки ответов базы знаний: у пользователя одна оценка на вопрос
+    "create table feedback (
+        question text not null,
+        user_id integer not null,
+        chat_id integer not null,
+        helpful integer not null,
+        created_at text not null default current_timestamp,
+        primary key (question, user_id)
+    );",
+    // Когда на вопрос ответили, для среднего времени ответа. У старых
+    // ответов точного времени нет, берём время последнего изменения
+    "alter table questions add column answered_at text;
+    update questions set answered_at = updated_at where answer is not null;",
+    // Вопросы, сохранённые после 👎 ответу базы знаний с тем же названием
+    "alter table questions add column unhelpful integer not null default 0;",
+];
+
+const TICKET_COLUMNS: &str = "id, chat_id, user_id, username, created_at, closed_at";
+
+const QUESTION_COLUMNS: &str = "id, user_id, chat_id, username, question, status, answer, created_at, updated_at, \
+                                delivery, delivery_error, delivered_at, cluster_id, asked, unhelpful";
+
+#[derive(Clone)]
 pub struct Database {
     path: String
 }
 
+/// Состояние сохранённого вопроса в очереди модерации
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionStatus {
+    /// Ещё никто не смотрел
+    #[default]
+    New,
+    /// Администратор счёл вопрос нужным, но ещё не ответил
+    Approved,
+    Answered,
+    Rejected,
+    /// Такой вопрос уже есть в базе знаний или в очереди
+    Duplicate,
+}
+
+impl QuestionStatus {
+    /// Вопросы, ждущие решения администратора
+    pub const OPEN: &'static [QuestionStatus] = &[QuestionStatus::New, QuestionStatus::Approved];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuestionStatus::New => "new",
+            QuestionStatus::Approved => "approved",
+            QuestionStatus::Answered => "answered",
+            QuestionStatus::Rejected => "rejected",
+            QuestionStatus::Duplicate => "duplicate",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        [Self::New, Self::Approved, Self::Answered, Self::Rejected, Self::Duplicate]
+            .into_iter()
+            .find(|known| known.as_str() == status)
+    }
+}
+
+impl ToSql for QuestionStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for QuestionStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let status = value.as_str()?;
+        Self::parse(status).ok_or_else(|| FromSqlError::Other(format!("неизвестный статус вопроса {status}").into()))
+    }
+}
+
+/// Доставлен ли автору вопроса ответ администратора
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Ответ в очереди исходящих сообщений
+    Pending,
+    Delivered,
+    /// Мессенджер отказался доставить ответ, например, пользователь заблокировал бота
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        [Self::Pending, Self::Delivered, Self::Failed]
+            .into_iter()
+            .find(|known| known.as_str() == status)
+    }
+}
+
+impl ToSql for DeliveryStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for DeliveryStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let status = value.as_str()?;
+        Self::parse(status).ok_or_else(|| FromSqlError::Other(format!("неизвестный статус доставки {status}").into()))
+    }
+}
+
+/// Вопрос, сохранённый по просьбе пользователя
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuestion {
+    pub id: i64,
+    pub user_id: u64,
+    /// Выгрузки старых версий не содержали полей ниже
+    #[serde(default)]
+    pub chat_id: i64,
+    #[serde(default)]
+    pub username: Option<String>,
+    pub question: String,
+    #[serde(default)]
+    pub status: QuestionStatus,
+    #[serde(default)]
+    pub answer: Option<String>,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: String,
+    /// Что с ответом автору, если на вопрос ответили
+    #[serde(default)]
+    pub delivery: Option<DeliveryStatus>,
+    #[serde(default)]
+    pub delivery_error: Option<String>,
+    #[serde(default)]
+    pub delivered_at: Option<String>,
+    /// Первый вопрос с той же формулировкой, если этот - его повтор
+    #[serde(default)]
+    pub cluster_id: Option<i64>,
+    /// Сколько раз задан вопрос вместе с повторами
+    #[serde(default)]
+    pub asked: u32,
+    /// Пользователю не помог ответ базы знаний на этот вопрос
+    #[serde(default)]
+    pub unhelpful: bool,
+}
+
+impl SavedQuestion {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(SavedQuestion {
+            id: row.get(0)?,
+            user_id: row.get(1)?,
+            chat_id: row.get(2)?,
+            username: row.get(3)?,
+            question: row.get(4)?,
+            status: row.get(5)?,
+            answer: row.get(6)?,
+            created_at: row.get(7)?,
+            updated_at: row.get(8)?,
+            delivery: row.get(9)?,
+            delivery_error: row.get(10)?,
+            delivered_at: row.get(11)?,
+            cluster_id: row.get(12)?,
+            asked: row.get(13)?,
+            unhelpful: row.get(14)?,
+        })
+    }
+}
+
+/// Действие пользователя, которое записывается для аналитики
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// Пользователь отправил команду
+    Command,
+    /// Пользователь открыл категорию
+    CategoryOpened,
+    /// Бот нашёл ответ на вопрос
+    QuestionAnswered,
+    /// Бот не понял, что от него хотят
+    UnknownQuery,
+    /// Пользователь согласился сохранить непонятый вопрос
+    SaveAccepted,
+    SaveDeclined,
+}
+
+impl EventKind {
+    /// Сообщения пользователя, которые обработал бот
+    pub const MESSAGES: &'static [EventKind] =
+        &[EventKind::Command, EventKind::CategoryOpened, EventKind::QuestionAnswered, EventKind::UnknownQuery];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Command => "command",
+            EventKind::CategoryOpened => "category",
+            EventKind::QuestionAnswered => "answer",
+            EventKind::UnknownQuery => "unknown",
+            EventKind::SaveAccepted => "save",
+            EventKind::SaveDeclined => "nosave",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        [Self::Command, Self::CategoryOpened, Self::QuestionAnswered, Self::UnknownQuery, Self::SaveAccepted, Self::SaveDeclined]
+            .into_iter()
+            .find(|known| known.as_str() == kind)
+    }
+}
+
+impl ToSql for EventKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+/// Запросы пользователей за период по тому, как бот их понял
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryCounts {
+    pub answered: usize,
+    pub categories: usize,
+    pub unknown: usize,
+}
+
+impl QueryCounts {
+    /// Доля запросов, которые бот не понял, от 0 до 1
+    pub fn unanswered_rate(&self) -> f64 {
+        match self.answered + self.categories + self.unknown {
+            0 => 0.0,
+            total => self.unknown as f64 / total as f64,
+        }
+    }
+}
+
+/// Сколько раз открыли категорию и сколько раз после этого
+/// больше ничего не выбрали в дереве
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropOff {
+    pub category: String,
+    pub opened: usize,
+    pub left: usize,
+}
+
+/// Оценки ответа на вопрос базы знаний
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Satisfaction {
+    pub question: String,
+    pub helpful: usize,
+    pub unhelpful: usize,
+}
+
+impl Satisfaction {
+    /// Доля оценок 👍 от 0 до 1
+    pub fn score(&self) -> f64 {
+        self.helpful as f64 / (self.helpful + self.unhelpful).max(1) as f64
+    }
+}
+
+/// Разговор пользователя с оператором
+#[derive(Debug, Clone)]
+pub struct Ticket {
+    pub id: i64,
+    /// Чат пользователя
+    pub chat_id: i64,
+    pub user_id: u64,
+    pub username: Option<String>,
+    pub created_at: String,
+    /// У открытого разговора нет
+    pub closed_at: Option<String>,
+}
+
+impl Ticket {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Ticket {
+            id: row.get(0)?,
+            chat_id: row.get(1)?,
+            user_id: row.get(2)?,
+            username: row.get(3)?,
+            created_at: row.get(4)?,
+            closed_at: row.get(5)?,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+}
+
 impl Database {
+    /// Открывает базу и применяет недостающие миграции
     pub fn new(path: &str) -> Result<Self, Box<dyn Error>> {
-        let path = path.to_owned();
+        let db = Self::open(path);
+        db.migrate()?;
 
-        let conn = Connection::open(&path)?;
+        Ok(db)
+    }
 
-        conn.execute(
-            "create table if not exists questions (
-                id integer primary key,
-                user_id integer not null,
-                question text not null
-            )", 
-            [],
-        )?;
+    /// Открывает базу как есть, без миграций
+    pub fn open(path: &str) -> Self {
+        Self { path: path.to_owned() }
+    }
+
+    /// Версия схемы базы и версия, до которой её обновит `migrate`
+    pub fn schema_version(&self) -> rusqlite::Result<(usize, usize)> {
+        let conn = Connection::open(&self.path)?;
+        let version: usize = conn.query_row("pragma user_version", [], |row| row.get(0))?;
+
+        Ok((version, MIGRATIONS.len()))
+    }
+
+    /// Применяет недостающие миграции, возвращает число применённых
+    pub fn migrate(&self) -> rusqlite::Result<usize> {
+        let mut conn = Connection::open(&self.path)?;
+        let (version, _) = self.schema_version()?;
+
+        let pending = MIGRATIONS.iter().enumerate().skip(version);
+        let mut applied = 0;
+        for (index, migration) in pending {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", index + 1)?;
+            tx.commit()?;
+            applied += 1;
+        }
 
         // соединение автоматически закрывается Drop'ом
-        Ok(Self { path })
+        Ok(applied)
     }
 
-    // Здесь я чуть было не начал расписывать полноценный 
-    // универсальный класс для работы с БД, но вовремя 
+    // Здесь я чуть было не начал расписывать полноценный
+    // универсальный класс для работы с БД, но вовремя
     // остановился. Не попадайтесь в tar pit!
-    pub fn insert_question(&mut self, question: &str, uid: u64) -> rusqlite::Result<()> {
+    /// Ставит вопрос в очередь модерации, возвращает его номер. Повтор
+    /// открытого вопроса в очередь не попадает: он добавляется в группу
+    /// того вопроса и увеличивает его счётчик. Сравниваются только тексты
+    /// вопросов, без пометки `unhelpful`
+    pub fn insert_question(&self, question: &str, uid: u64, chat_id: i64, username: Option<&str>, unhelpful: bool) -> rusqlite::Result<i64> {
+        let mut conn = Connection::open(&self.path)?;
+        let tx = conn.transaction()?;
+
+        match Self::same_open_question(&tx, question)? {
+            Some(cluster_id) => {
+                tx.execute(
+                    "insert into questions (user_id, chat_id, username, question, status, cluster_id, unhelpful)
+                     values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![&uid, &chat_id, &username, &question.to_owned(), &QuestionStatus::Duplicate, &cluster_id, &unhelpful],
+                )?;
+                tx.execute(
+                    "update questions set asked = asked + 1, updated_at = current_timestamp where id = ?1",
+                    params![&cluster_id],
+                )?;
+            }
+            None => {
+                tx.execute(
+                    "insert into questions (user_id, chat_id, username, question, unhelpful) values (?1, ?2, ?3, ?4, ?5)",
+                    params![&uid, &chat_id, &username, &question.to_owned(), &unhelpful],
+                )?;
+            }
+        }
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    /// Открытый вопрос, повтором которого является `question`. Повтором считается
+    /// только тот же текст с точностью до регистра и знаков препинания: ответ на
+    /// него уйдёт всем повторам, а похожие формулировки бывают и у разных вопросов
+    fn same_open_question(conn: &Connection, question: &str) -> rusqlite::Result<Option<i64>> {
+        let mut stmt = conn.prepare(&format!(
+            "select id, question from questions where status in ({}) and cluster_id is null order by id",
+            status_list(QuestionStatus::OPEN)
+        ))?;
+        let open = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+                       .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let question = similarity::normalize(question);
+        Ok(open.into_iter().find(|(_, text)| similarity::normalize(text) == question).map(|(id, _)| id))
+    }
+
+    /// Повторы вопроса `id`
+    pub fn cluster(&self, id: i64) -> rusqlite::Result<Vec<SavedQuestion>> {
         let conn = Connection::open(&self.path)?;
 
+        let mut stmt = conn.prepare(&format!("select {QUESTION_COLUMNS} from questions where cluster_id = ?1 order by id"))?;
+        let rows = stmt.query_map(params![&id], SavedQuestion::from_row)?;
+
+        rows.collect()
+    }
+
+    pub fn questions(&self) -> rusqlite::Result<Vec<SavedQuestion>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare(&format!("select {QUESTION_COLUMNS} from questions order by id"))?;
+        let rows = stmt.query_map([], SavedQuestion::from_row)?;
+
+        rows.collect()
+    }
+
+    /// Страница вопросов с одним из статусов: сначала самые частые,
+    /// среди одинаково частых - от старых к новым
+    pub fn questions_page(&self, statuses: &[QuestionStatus], offset: usize, limit: usize) -> rusqlite::Result<Vec<SavedQuestion>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare(&format!(
+            "select {QUESTION_COLUMNS} from questions where status in ({}) order by asked desc, id limit ?1 offset ?2",
+            status_list(statuses)
+        ))?;
+        let rows = stmt.query_map(params![&limit, &offset], SavedQuestion::from_row)?;
+
+        rows.collect()
+    }
+
+    /// Число вопросов с одним из статусов
+    pub fn count_with_status(&self, statuses: &[QuestionStatus]) -> rusqlite::Result<usize> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            &format!("select count(*) from questions where status in ({})", status_list(statuses)),
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Меняет статус вопроса; `false`, если такого вопроса нет
+    pub fn set_status(&self, id: i64, status: QuestionStatus) -> rusqlite::Result<bool> {
+        let conn = Connection::open(&self.path)?;
+        let changed = conn.execute(
+            "update questions set status = ?1, updated_at = current_timestamp where id = ?2",
+            params![&status, &id],
+        )?;
+
+        Ok(changed > 0)
+    }
+
+    /// Записывает ответ на вопрос, который ещё предстоит доставить автору.
+    /// `status` - `Answered` или `Duplicate`, если ответ взят из базы знаний;
+    /// `false`, если такого вопроса нет
+    pub fn answer_question(&self, id: i64, status: QuestionStatus, answer: &str) -> rusqlite::Result<bool> {
+        let conn = Connection::open(&self.path)?;
+        let changed = conn.execute(
+            "update questions
+             set status = ?1, answer = ?2, delivery = ?3, delivery_error = null, delivered_at = null,
+                 answered_at = current_timestamp, updated_at = current_timestamp
+             where id = ?4",
+            params![&status, &answer, &DeliveryStatus::Pending, &id],
+        )?;
+
+        Ok(changed > 0)
+    }
+
+    /// Записывает, удалось ли доставить автору ответ на вопрос
+    pub fn record_delivery(&self, id: i64, result: Result<(), String>) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
+        match result {
+            Ok(()) => conn.execute(
+                "update questions set delivery = ?1, delivery_error = null, delivered_at = current_timestamp where id = ?2",
+                params![&DeliveryStatus::Delivered, &id],
+            )?,
+            Err(error) => conn.execute(
+                "update questions set delivery = ?1, delivery_error = ?2 where id = ?3",
+                params![&DeliveryStatus::Failed, &error, &id],
+            )?,
+        };
+
+        Ok(())
+    }
+
+    /// Запоминает сообщение в чате администраторов, показывающее вопрос
+    pub fn remember_forward(&self, chat_id: i64, message_id: i32, question_id: i64) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
         conn.execute(
-            "insert into questions (user_id, question) values (?1, ?2)",
-            params![&uid, &question.to_owned()],
+            "insert or replace into forwarded (chat_id, message_id, question_id) values (?1, ?2, ?3)",
+            params![&chat_id, &message_id, &question_id],
         )?;
 
         Ok(())
     }
+
+    /// Вопрос, который показывает сообщение `message_id` в чате администраторов
+    pub fn forwarded_question(&self, chat_id: i64, message_id: i32) -> rusqlite::Result<Option<i64>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            "select question_id from forwarded where chat_id = ?1 and message_id = ?2",
+            params![&chat_id, &message_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// Добавляет ранее выгруженные вопросы; вопросы с уже занятым id
+    /// пропускаются. Возвращает число добавленных
+    pub fn import_questions(&self, questions: &[SavedQuestion]) -> rusqlite::Result<usize> {
+        let mut conn = Connection::open(&self.path)?;
+        let tx = conn.transaction()?;
+
+        let mut imported = 0;
+        for question in questions {
+            // В старых выгрузках нет чата: вопросы задавали в личных чатах
+            let chat_id = if question.chat_id == 0 { question.user_id as i64 } else { question.chat_id };
+            imported += tx.execute(
+                "insert or ignore into questions
+                    (id, user_id, chat_id, username, question, status, answer, delivery, delivery_error, delivered_at,
+                     cluster_id, asked, unhelpful)
+                 values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![&question.id, &question.user_id, &chat_id, &question.username,
+                        &question.question, &question.status, &question.answer,
+                        &question.delivery, &question.delivery_error, &question.delivered_at,
+                        &question.cluster_id, &question.asked.max(1), &question.unhelpful],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(imported)
+    }
+
+    pub fn question(&self, id: i64) -> rusqlite::Result<Option<SavedQuestion>> {
+        let conn = Connection::open(&self.path)?;
+
+        conn.query_row(
+            &format!("select {QUESTION_COLUMNS} from questions where id = ?1"),
+            params![&id],
+            SavedQuestion::from_row,
+        ).optional()
+    }
+
+    pub fn count_questions(&self) -> rusqlite::Result<usize> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row("select count(*) from questions", [], |row| row.get(0))
+    }
+
+    /// Запоминает чат, чтобы потом можно было сделать рассылку
+    pub fn remember_chat(&self, chat_id: i64) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute("insert or ignore into chats (id) values (?1)", params![&chat_id])?;
+
+        Ok(())
+    }
+
+    /// Открывает разговор с оператором и возвращает его номер
+    pub fn open_ticket(&self, chat_id: i64, user_id: u64, username: Option<&str>) -> rusqlite::Result<i64> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute(
+            "insert into tickets (chat_id, user_id, username) values (?1, ?2, ?3)",
+            params![&chat_id, &user_id, &username],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn ticket(&self, id: i64) -> rusqlite::Result<Option<Ticket>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            &format!("select {TICKET_COLUMNS} from tickets where id = ?1"),
+            params![&id],
+            Ticket::from_row,
+        ).optional()
+    }
+
+    /// Открытый разговор с оператором в чате пользователя
+    pub fn open_ticket_in(&self, chat_id: i64) -> rusqlite::Result<Option<Ticket>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            &format!("select {TICKET_COLUMNS} from tickets where chat_id = ?1 and closed_at is null order by id desc limit 1"),
+            params![&chat_id],
+            Ticket::from_row,
+        ).optional()
+    }
+
+    /// Закрывает разговор; `false`, если он уже закрыт или его нет
+    pub fn close_ticket(&self, id: i64) -> rusqlite::Result<bool> {
+        let conn = Connection::open(&self.path)?;
+        let changed = conn.execute(
+            "update tickets set closed_at = current_timestamp where id = ?1 and closed_at is null",
+            params![&id],
+        )?;
+
+        Ok(changed > 0)
+    }
+
+    /// Запоминает сообщение в чате операторов, относящееся к разговору
+    pub fn remember_relay(&self, chat_id: i64, message_id: i32, ticket_id: i64) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute(
+            "insert or replace into relayed (chat_id, message_id, ticket_id) values (?1, ?2, ?3)",
+            params![&chat_id, &message_id, &ticket_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Разговор, к которому относится сообщение `message_id` в чате операторов
+    pub fn relayed_ticket(&self, chat_id: i64, message_id: i32) -> rusqlite::Result<Option<i64>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            "select ticket_id from relayed where chat_id = ?1 and message_id = ?2",
+            params![&chat_id, &message_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// Записывает действие пользователя; `label` - категория, вопрос или текст запроса
+    pub fn record_event(&self, kind: EventKind, chat_id: i64, user_id: u64, label: Option<&str>) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute(
+            "insert into events (kind, chat_id, user_id, label) values (?1, ?2, ?3, ?4)",
+            params![&kind, &chat_id, &user_id, &label],
+        )?;
+
+        Ok(())
+    }
+
+    /// Период из `days` дней, кончающийся днём `to` (по умолчанию сегодня
+    /// по часам базы, UTC), в виде дат `ГГГГ-ММ-ДД` для запросов аналитики
+    pub fn period(&self, to: Option<&str>, days: u32) -> rusqlite::Result<(String, String)> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            "select date(coalesce(?1, 'now'), ?2), date(coalesce(?1, 'now'))",
+            params![&to, &format!("-{} days", days.saturating_sub(1))],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    /// Самые частые категории, вопросы или запросы (смотря по `kind`)
+    /// с `from` по `to` включительно, вместе с числом событий
+    pub fn top_labels(&self, kind: EventKind, from: &str, to: &str, limit: usize) -> rusqlite::Result<Vec<(String, usize)>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare(
+            "select label, count(*) as times from events
+             where kind = ?1 and date(created_at) between ?2 and ?3
+             group by label order by times desc, label limit ?4"
+        )?;
+        let rows = stmt.query_map(params![&kind, &from, &to, &limit], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        rows.collect()
+    }
+
+    /// Сколько разных пользователей писало боту в каждый день периода
+    pub fn daily_users(&self, from: &str, to: &str) -> rusqlite::Result<Vec<(String, usize)>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare(
+            "select date(created_at) as day, count(distinct user_id) from events
+             where date(created_at) between ?1 and ?2
+             group by day order by day"
+        )?;
+        let rows = stmt.query_map(params![&from, &to], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        rows.collect()
+    }
+
+    /// Сколько сообщений пользователей обработал бот за период
+    pub fn count_messages(&self, from: &str, to: &str) -> rusqlite::Result<usize> {
+        let conn = Connection::open(&self.path)?;
+        let kinds = EventKind::MESSAGES.iter()
+                                       .map(|kind| format!("'{}'", kind.as_str()))
+                                       .collect::<Vec<_>>()
+                                       .join(", ");
+        conn.query_row(
+            &format!("select count(*) from events where kind in ({kinds}) and date(created_at) between ?1 and ?2"),
+            params![&from, &to],
+            |row| row.get(0),
+        )
+    }
+
+    /// Среднее время в секундах от сохранения вопроса до ответа
+    /// администратора для вопросов, отвеченных за период
+    pub fn mean_answer_time(&self, from: &str, to: &str) -> rusqlite::Result<Option<f64>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            "select avg((julianday(answered_at) - julianday(created_at)) * 86400) from questions
+             where status = ?1 and date(answered_at) between ?2 and ?3",
+            params![&QuestionStatus::Answered, &from, &to],
+            |row| row.get(0),
+        )
+    }
+
+    /// Запросы пользователей с `from` по `to` включительно
+    pub fn query_counts(&self, from: &str, to: &str) -> rusqlite::Result<QueryCounts> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare("select kind, count(*) from events where date(created_at) between ?1 and ?2 group by kind")?;
+        let rows = stmt.query_map(params![&from, &to], |row| Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?)))?;
+
+        let mut counts = QueryCounts::default();
+        for row in rows {
+            let (kind, count) = row?;
+            match EventKind::parse(&kind) {
+                Some(EventKind::QuestionAnswered) => counts.answered = count,
+                Some(EventKind::CategoryOpened) => counts.categories = count,
+                Some(EventKind::UnknownQuery) => counts.unknown = count,
+                _ => (),
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Категории, открытые с `from` по `to` включительно, и сколько раз
+    /// следующим действием в том же чате не стал выбор в дереве. Сначала
+    /// категории, из которых уходят чаще всего
+    pub fn category_drop_off(&self, from: &str, to: &str) -> rusqlite::Result<Vec<DropOff>> {
+        let conn = Connection::open(&self.path)?;
+
+        // Следующее действие ищем по всем событиям, а не только за период
+        let mut stmt = conn.prepare(
+            "select label, count(*), sum(coalesce(next_kind, '') not in (?1, ?2)) as left from (
+                select kind, label, created_at, lead(kind) over (partition by chat_id order by id) as next_kind
+                from events
+             )
+             where kind = ?1 and date(created_at) between ?3 and ?4
+             group by label order by left desc, label"
+        )?;
+        let rows = stmt.query_map(
+            params![&EventKind::CategoryOpened, &EventKind::QuestionAnswered, &from, &to],
+            |row| Ok(DropOff { category: row.get(0)?, opened: row.get(1)?, left: row.get(2)? }),
+        )?;
+
+        rows.collect()
+    }
+
+    /// Записывает оценку ответа на вопрос базы знаний, заменяя прежнюю оценку пользователя
+    pub fn record_vote(&self, question: &str, chat_id: i64, user_id: u64, helpful: bool) -> rusqlite::Result<()> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute(
+            "insert or replace into feedback (question, user_id, chat_id, helpful) values (?1, ?2, ?3, ?4)",
+            params![&question, &user_id, &chat_id, &helpful],
+        )?;
+
+        Ok(())
+    }
+
+    /// Оценка пользователем ответа на вопрос; `None`, если он не оценивал
+    pub fn vote(&self, question: &str, user_id: u64) -> rusqlite::Result<Option<bool>> {
+        let conn = Connection::open(&self.path)?;
+        conn.query_row(
+            "select helpful from feedback where question = ?1 and user_id = ?2",
+            params![&question, &user_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// Оценки всех оценённых вопросов, сначала с наибольшей долей 👎
+    pub fn satisfaction(&self) -> rusqlite::Result<Vec<Satisfaction>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare(
+            "select question, sum(helpful), sum(not helpful) from feedback
+             group by question order by avg(helpful), count(*) desc, question"
+        )?;
+        let rows = stmt.query_map([], |row| Ok(Satisfaction { question: row.get(0)?, helpful: row.get(1)?, unhelpful: row.get(2)? }))?;
+
+        rows.collect()
+    }
+
+    /// Все чаты, в которых писали боту
+    pub fn chats(&self) -> rusqlite::Result<Vec<i64>> {
+        let conn = Connection::open(&self.path)?;
+
+        let mut stmt = conn.prepare("select id from chats order by id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        rows.collect()
+    }
+}
+
+/// Статусы для `where status in (...)`. Строки статусов - константы, не ввод пользователя
+fn status_list(statuses: &[QuestionStatus]) -> String {
+    statuses.iter()
+            .map(|status| format!("'{}'", status.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
 }
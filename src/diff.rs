@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use crate::serde::QASerde;
+
+/// Изменение одного вопроса между двумя версиями базы знаний
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added    { path: Vec<String>, question: String },
+    Removed  { path: Vec<String>, question: String },
+    Moved    { from: Vec<String>, to: Vec<String>, question: String },
+    Reworded { path: Vec<String>, old: String, new: String },
+    AnswerChanged { path: Vec<String>, question: String, old: String, new: String },
+}
+
+/// Разница между двумя версиями базы знаний
+#[derive(Debug, Default)]
+pub struct TreeDiff {
+    pub changes: Vec<Change>,
+}
+
+/// Вопрос однозначно определяется категорией и названием: одноимённые
+/// вопросы в разных категориях - разные вопросы
+type QuestionKey = (Vec<String>, String);
+
+fn collect_questions(qna: &QASerde) -> BTreeMap<QuestionKey, String> {
+    qna.tree()
+       .iter()
+       .filter(|(_, node)| node.is_question())
+       .map(|(id, node)| ((qna.get_path(id), node.label.clone()), node.answer.clone().unwrap_or_default()))
+       .collect()
+}
+
+impl TreeDiff {
+    /// Сравнивает старую и новую версии базы знаний. Вопросы сопоставляются
+    /// по категории и тексту; вопрос, исчезнувший из одной категории и
+    /// появившийся в другой, считается перемещённым, а исчезнувший и
+    /// появившийся вопрос в одной категории с одинаковым ответом -
+    /// переформулировкой
+    pub fn compute(old: &QASerde, new: &QASerde) -> Self {
+        let old_questions = collect_questions(old);
+        let mut new_questions = collect_questions(new);
+        let mut changes = vec![];
+        let mut removed = vec![];
+
+        for ((path, question), old_answer) in old_questions {
+            match new_questions.remove(&(path.clone(), question.clone())) {
+                Some(new_answer) if new_answer != old_answer =>
+                    changes.push(Change::AnswerChanged { path, question, old: old_answer, new: new_answer }),
+                Some(_) => (),
+                None => removed.push((path, question, old_answer)),
+            }
+        }
+
+        // Сначала перемещения: тот же вопрос в другой категории
+        let mut unmatched = vec![];
+        for (path, question, old_answer) in removed {
+            let moved = new_questions.keys()
+                                     .find(|(_, new_question)| *new_question == question)
+                                     .cloned();
+
+            match moved {
+                Some(key) => {
+                    let new_answer = new_questions.remove(&key).unwrap_or_default();
+                    let (to, question) = key;
+                    changes.push(Change::Moved { from: path, to: to.clone(), question: question.clone() });
+                    if new_answer != old_answer {
+                        changes.push(Change::AnswerChanged { path: to, question, old: old_answer, new: new_answer });
+                    }
+                }
+                None => unmatched.push((path, question, old_answer)),
+            }
+        }
+
+        for (path, question, old_answer) in unmatched {
+            let reworded = new_questions.iter()
+                                        .find(|((new_path, _), answer)| *new_path == path && **answer == old_answer)
+                                        .map(|(key, _)| key.clone());
+
+            match reworded {
+                Some(key) => {
+                    new_questions.remove(&key);
+                    changes.push(Change::Reworded { path, old: question, new: key.1 });
+                }
+                None => changes.push(Change::Removed { path, question }),
+            }
+        }
+
+        for ((path, question), _) in new_questions {
+            changes.push(Change::Added { path, question });
+        }
+
+        TreeDiff { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn format_path(path: &[String]) -> String {
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.join(" / ")
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { path, question } =>
+                write!(f, "+ [{}] \"{question}\"", format_path(path)),
+            Change::Removed { path, question } =>
+                write!(f, "- [{}] \"{question}\"", format_path(path)),
+            Change::Moved { from, to, question } =>
+                write!(f, "> \"{question}\": [{}] -> [{}]", format_path(from), format_path(to)),
+            Change::Reworded { path, old, new } =>
+                write!(f, "~ [{}] \"{old}\" -> \"{new}\"", format_path(path)),
+            Change::AnswerChanged { path, question, old, new } =>
+                write!(f, "* [{}] \"{question}\": \"{old}\" -> \"{new}\"", format_path(path)),
+        }
+    }
+}
+
+impl Display for TreeDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Изменений нет.");
+        }
+
+        writeln!(f, "Изменений: {}", self.changes.len())?;
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn qna(value: serde_json::Value) -> QASerde {
+        QASerde::from_json(json!({ "root": value })).unwrap()
+    }
+
+    fn path(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn no_changes() {
+        let old = qna(json!({ "А": { "Вопрос?": "Ответ" } }));
+        let new = qna(json!({ "А": { "Вопрос?": "Ответ" } }));
+
+        assert!(TreeDiff::compute(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = qna(json!({ "А": { "Старый?": "1" } }));
+        let new = qna(json!({ "А": { "Новый?": "2" } }));
+
+        assert_eq!(TreeDiff::compute(&old, &new).changes, [
+            Change::Removed { path: path(&["А"]), question: "Старый?".to_string() },
+            Change::Added { path: path(&["А"]), question: "Новый?".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn changed_answer() {
+        let old = qna(json!({ "А": { "Вопрос?": "Старый ответ" } }));
+        let new = qna(json!({ "А": { "Вопрос?": "Новый ответ" } }));
+
+        assert_eq!(TreeDiff::compute(&old, &new).changes, [Change::AnswerChanged {
+            path: path(&["А"]),
+            question: "Вопрос?".to_string(),
+            old: "Старый ответ".to_string(),
+            new: "Новый ответ".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn moved() {
+        let old = qna(json!({ "А": { "Вопрос?": "Ответ" }, "Б": { "Другой?": "2" } }));
+        let new = qna(json!({ "А": { "Другой?": "3" }, "Б": { "Вопрос?": "Ответ", "Другой?": "2" } }));
+
+        assert_eq!(TreeDiff::compute(&old, &new).changes, [
+            Change::Moved { from: path(&["А"]), to: path(&["Б"]), question: "Вопрос?".to_string() },
+            Change::Added { path: path(&["А"]), question: "Другой?".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn reworded() {
+        let old = qna(json!({ "А": { "Когда сессия?": "В январе" } }));
+        let new = qna(json!({ "А": { "Когда начинается сессия?": "В январе" } }));
+
+        assert_eq!(TreeDiff::compute(&old, &new).changes, [Change::Reworded {
+            path: path(&["А"]),
+            old: "Когда сессия?".to_string(),
+            new: "Когда начинается сессия?".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn same_question_in_different_categories() {
+        let old = qna(json!({
+            "А": { "Где деканат?": "Корпус 1" },
+            "Б": { "Где деканат?": "Корпус 2" },
+        }));
+        let new = qna(json!({
+            "А": { "Где деканат?": "Корпус 3" },
+            "В": { "Где деканат?": "Корпус 4" },
+        }));
+
+        assert_eq!(TreeDiff::compute(&old, &new).changes, [
+            Change::AnswerChanged {
+                path: path(&["А"]),
+                question: "Где деканат?".to_string(),
+                old: "Корпус 1".to_string(),
+                new: "Корпус 3".to_string(),
+            },
+            Change::Moved { from: path(&["Б"]), to: path(&["В"]), question: "Где деканат?".to_string() },
+            Change::AnswerChanged {
+                path: path(&["В"]),
+                question: "Где деканат?".to_string(),
+                old: "Корпус 2".to_string(),
+                new: "Корпус 4".to_string(),
+            },
+        ]);
+    }
+}
@@ -50,3 +50,84 @@ pub mod serde {
         }
     }
 }
+
+pub mod snapshot {
+    use super::*;
+
+    #[derive(Debug)]
+    pub enum SnapshotError {
+        /// Файл не является снапшотом
+        BadMagic,
+        UnsupportedVersion(u16),
+        ChecksumMismatch,
+        /// Снапшот собран из другой версии JSON-файла
+        Stale,
+        Truncated,
+        Corrupted,
+        Io(std::io::Error),
+    }
+
+    impl Error for SnapshotError { }
+
+    impl Display for SnapshotError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                SnapshotError::BadMagic => write!(f, "Файл не является снапшотом базы знаний."),
+                SnapshotError::UnsupportedVersion(v) => write!(f, "Неподдерживаемая версия снапшота: {v}"),
+                SnapshotError::ChecksumMismatch => write!(f, "Контрольная сумма снапшота не совпадает."),
+                SnapshotError::Stale => write!(f, "Снапшот устарел: JSON-файл базы знаний изменился."),
+                SnapshotError::Truncated => write!(f, "Снапшот повреждён: неожиданный конец данных."),
+                SnapshotError::Corrupted => write!(f, "Снапшот повреждён: недопустимые данные."),
+                SnapshotError::Io(e) => write!(f, "Ошибка чтения снапшота: {e}"),
+            }
+        }
+    }
+
+    impl From<std::io::Error> for SnapshotError {
+        fn from(error: std::io::Error) -> Self {
+            SnapshotError::Io(error)
+        }
+    }
+}
+
+pub mod config {
+    use super::*;
+
+    /// Все найденные при запуске проблемы конфигурации
+    #[derive(Debug)]
+    pub struct ConfigError { pub problems: Vec<String> }
+
+    impl ConfigError {
+        pub fn single(problem: String) -> Self {
+            ConfigError { problems: vec![problem] }
+        }
+    }
+
+    impl Error for ConfigError { }
+
+    impl Display for ConfigError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Ошибка конфигурации:")?;
+            for problem in &self.problems {
+                write!(f, "\n  - {problem}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub mod scenario {
+    use super::*;
+
+    /// Файл сценария, который нельзя выполнить
+    #[derive(Debug)]
+    pub struct ScenarioError { pub path: String, pub problem: String }
+
+    impl Error for ScenarioError { }
+
+    impl Display for ScenarioError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Неверный сценарий {}: {}", self.path, self.problem)
+        }
+    }
+}
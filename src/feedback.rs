@@ -0,0 +1,61 @@
+use crate::conversation::{ButtonPress, Conversation, InlineButton, Keyboard, Reply};
+use crate::util::logging::non_fatal;
+
+/// Кнопки оценки присылают `feedback:<up|down>:<crc32 вопроса>`. Номер узла
+/// после перезагрузки базы может достаться другому вопросу, а хеш - нет
+const PREFIX: &str = "feedback:";
+
+/// Сообщение "Ответ помог?" с кнопками оценки ответа на вопрос `question`
+pub fn prompt(conversation: &Conversation, chat_id: i64, question: &str) -> Reply {
+    let messages = conversation.messages();
+    let key = crc32fast::hash(question.as_bytes());
+    let buttons = vec![
+        InlineButton { text: messages.button_helpful.clone(), data: format!("{PREFIX}up:{key:08x}") },
+        InlineButton { text: messages.button_unhelpful.clone(), data: format!("{PREFIX}down:{key:08x}") },
+    ];
+
+    Reply::Message { chat_id, text: messages.feedback_prompt.clone(), keyboard: Some(Keyboard::Inline(buttons)) }
+}
+
+/// Записывает оценку ответа; после 👎 предлагает сохранить вопрос.
+/// `None`, если это другая кнопка
+pub fn handle_button(conversation: &Conversation, press: &ButtonPress) -> Option<Vec<Reply>> {
+    let (vote, key) = press.data.strip_prefix(PREFIX)?.split_once(':')?;
+    let helpful = vote == "up";
+    let messages = conversation.messages();
+
+    let mut replies = vec![Reply::Notify { press_id: press.id.clone(), text: messages.feedback_thanks.clone() }];
+    replies.extend(press.message_id.map(|message_id| Reply::RemoveButtons { chat_id: press.chat_id, message_id }));
+
+    // Вопрос могли убрать из базы, пока пользователь думал
+    let Some(question) = question_by_key(conversation, key) else {
+        return Some(replies);
+    };
+    if let Err(error) = conversation.question_db().record_vote(&question, press.chat_id, press.user_id, helpful) {
+        non_fatal(error);
+    }
+
+    if !helpful {
+        // Сохранится сам вопрос с пометкой, что ответ на него не помог
+        conversation.offer_save_unhelpful(press.chat_id, press.user_id, question);
+        replies.push(Reply::Message {
+            chat_id: press.chat_id,
+            text: messages.feedback_save.clone(),
+            keyboard: Some(conversation.save_keyboard()),
+        });
+    }
+
+    Some(replies)
+}
+
+/// Вопрос базы знаний, хеш которого записан в кнопке
+fn question_by_key(conversation: &Conversation, key: &str) -> Option<String> {
+    let key = u32::from_str_radix(key, 16).ok()?;
+    let qna = conversation.knowledge().load();
+
+    qna.question_id
+       .iter()
+       .filter_map(|&id| qna.get_node(id))
+       .find(|node| crc32fast::hash(node.label.as_bytes()) == key)
+       .map(|node| node.label.clone())
+}
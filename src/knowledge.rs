@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::metrics::METRICS;
+use crate::serde::QASerde;
+
+/// Общая для всех обработчиков база знаний. Читатели берут неизменяемый
+/// снимок дерева без блокировок, а перезагрузка атомарно подменяет его
+/// целиком: уже начатые обработчики дорабатывают со старой версией
+#[derive(Clone)]
+pub struct KnowledgeBase {
+    current: Arc<ArcSwap<QASerde>>,
+}
+
+impl KnowledgeBase {
+    pub fn new(qna: QASerde) -> Self {
+        METRICS.knowledge_loaded();
+        KnowledgeBase { current: Arc::new(ArcSwap::from_pointee(qna)) }
+    }
+
+    /// Текущая версия базы знаний
+    pub fn load(&self) -> Arc<QASerde> {
+        self.current.load_full()
+    }
+
+    /// Подменяет базу знаний, возвращая предыдущую версию
+    pub fn swap(&self, qna: QASerde) -> Arc<QASerde> {
+        METRICS.knowledge_loaded();
+        self.current.swap(Arc::new(qna))
+    }
+}
+
+// База знаний передаётся между потоками обработчиков
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<KnowledgeBase>();
+};
@@ -1,21 +1,35 @@
 #![allow(dead_code)]
 
 use std::error::Error;
+
+use clap::Parser;
+
 mod telegram;
 mod serde;
 mod util;
 mod arenatree;
 mod error;
 mod db;
-
-static TOKEN:   &str = "";
-static DBPATH:  &str = "/mnt/c/Users/user/projects/mr-deeds/questions.db";
-static QNAPATH: &str = "/mnt/c/Users/user/projects/mr-deeds/db.json";
+mod diff;
+mod snapshot;
+mod knowledge;
+mod telegram_async;
+mod webhook;
+mod polling;
+mod outbox;
+mod config;
+mod cli;
+mod console;
+mod conversation;
+mod transport;
+mod scenario;
+mod admin;
+mod operator;
+mod similarity;
+mod feedback;
+mod report;
+mod metrics;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let suggestions_db = db::Database::new(DBPATH)?;
-    let mut bot = telegram::TelegramSender::new(TOKEN, QNAPATH, suggestions_db);
-    bot.start_reply_loop();
-
-    Ok(())
+    cli::Cli::parse().run()
 }
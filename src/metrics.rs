@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::db::EventKind;
+use crate::knowledge::KnowledgeBase;
+use crate::util::logging::{check_result, non_fatal};
+
+/// Счётчики одни на процесс: их увеличивают обработчики, очередь
+/// исходящих вызовов и цикл получения обновлений из разных потоков
+pub static METRICS: Metrics = Metrics::new();
+
+/// Границы корзин гистограммы длительности getUpdates, секунды. Запрос
+/// без обновлений висит до polling.timeout, поэтому корзины крупные
+const POLL_BUCKETS: [f64; 8] = [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Гистограмма в формате Prometheus: число наблюдений до каждой границы
+struct Histogram {
+    buckets: [u64; POLL_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+/// Метрики работы бота для Prometheus
+pub struct Metrics {
+    updates: AtomicU64,
+    /// Успешные и неудачные вызовы Bot API по методам
+    sent: Mutex<BTreeMap<&'static str, u64>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// Запросы пользователей по тому, как бот их понял
+    answered: AtomicU64,
+    categories: AtomicU64,
+    unknown: AtomicU64,
+    poll_duration: Mutex<Histogram>,
+    /// Время последнего успешного getUpdates и загрузки базы знаний, секунды Unix
+    last_poll: AtomicU64,
+    last_reload: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            updates: AtomicU64::new(0),
+            sent: Mutex::new(BTreeMap::new()),
+            errors: Mutex::new(BTreeMap::new()),
+            answered: AtomicU64::new(0),
+            categories: AtomicU64::new(0),
+            unknown: AtomicU64::new(0),
+            poll_duration: Mutex::new(Histogram { buckets: [0; POLL_BUCKETS.len()], sum: 0.0, count: 0 }),
+            last_poll: AtomicU64::new(0),
+            last_reload: AtomicU64::new(0),
+        }
+    }
+
+    pub fn update_received(&self) {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Вызов Bot API из очереди исходящих: ответ, уведомление, правка сообщения
+    pub fn api_call(&self, method: &'static str, ok: bool) {
+        let counters = if ok { &self.sent } else { &self.errors };
+        *counters.lock().unwrap().entry(method).or_default() += 1;
+    }
+
+    /// Запрос getUpdates: сколько он занял и удался ли
+    pub fn poll(&self, duration: Duration, ok: bool) {
+        if !ok {
+            self.api_call("getUpdates", false);
+            return;
+        }
+        self.last_poll.store(unix_now(), Ordering::Relaxed);
+
+        let seconds = duration.as_secs_f64();
+        let mut histogram = self.poll_duration.lock().unwrap();
+        for (bucket, &bound) in histogram.buckets.iter_mut().zip(&POLL_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Действие пользователя из аналитики; считаются только запросы
+    pub fn event(&self, kind: EventKind) {
+        let counter = match kind {
+            EventKind::QuestionAnswered => &self.answered,
+            EventKind::CategoryOpened => &self.categories,
+            EventKind::UnknownQuery => &self.unknown,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn knowledge_loaded(&self) {
+        self.last_reload.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Секунды Unix последнего успешного getUpdates; 0, если его ещё не было
+    pub fn last_poll(&self) -> u64 {
+        self.last_poll.load(Ordering::Relaxed)
+    }
+
+    /// Метрики в текстовом формате Prometheus
+    pub fn render(&self, knowledge: &KnowledgeBase) -> String {
+        let mut out = String::new();
+        // Запись в String не ошибается
+        let _ = self.write(&mut out, knowledge);
+        out
+    }
+
+    fn write(&self, out: &mut String, knowledge: &KnowledgeBase) -> std::fmt::Result {
+        writeln!(out, "# HELP bot_updates_received_total Обновления, полученные от Telegram")?;
+        writeln!(out, "# TYPE bot_updates_received_total counter")?;
+        writeln!(out, "bot_updates_received_total {}", self.updates.load(Ordering::Relaxed))?;
+
+        writeln!(out, "# HELP bot_replies_sent_total Успешные вызовы Bot API из очереди исходящих")?;
+        writeln!(out, "# TYPE bot_replies_sent_total counter")?;
+        for (method, count) in self.sent.lock().unwrap().iter() {
+            writeln!(out, "bot_replies_sent_total{{method=\"{method}\"}} {count}")?;
+        }
+
+        writeln!(out, "# HELP bot_api_errors_total Ошибки вызовов Bot API")?;
+        writeln!(out, "# TYPE bot_api_errors_total counter")?;
+        for (method, count) in self.errors.lock().unwrap().iter() {
+            writeln!(out, "bot_api_errors_total{{method=\"{method}\"}} {count}")?;
+        }
+
+        writeln!(out, "# HELP bot_get_updates_duration_seconds Длительность успешных запросов getUpdates")?;
+        writeln!(out, "# TYPE bot_get_updates_duration_seconds histogram")?;
+        {
+            let histogram = self.poll_duration.lock().unwrap();
+            for (bound, count) in POLL_BUCKETS.iter().zip(histogram.buckets) {
+                writeln!(out, "bot_get_updates_duration_seconds_bucket{{le=\"{bound}\"}} {count}")?;
+            }
+            writeln!(out, "bot_get_updates_duration_seconds_bucket{{le=\"+Inf\"}} {}", histogram.count)?;
+            writeln!(out, "bot_get_updates_duration_seconds_sum {}", histogram.sum)?;
+            writeln!(out, "bot_get_updates_duration_seconds_count {}", histogram.count)?;
+        }
+
+        let (answered, categories, unknown) = (self.answered.load(Ordering::Relaxed),
+                                               self.categories.load(Ordering::Relaxed),
+                                               self.unknown.load(Ordering::Relaxed));
+        writeln!(out, "# HELP bot_queries_total Запросы пользователей по тому, как бот их понял")?;
+        writeln!(out, "# TYPE bot_queries_total counter")?;
+        writeln!(out, "bot_queries_total{{result=\"answered\"}} {answered}")?;
+        writeln!(out, "bot_queries_total{{result=\"category\"}} {categories}")?;
+        writeln!(out, "bot_queries_total{{result=\"unknown\"}} {unknown}")?;
+        writeln!(out, "# HELP bot_unknown_query_ratio Доля непонятых запросов с запуска")?;
+        writeln!(out, "# TYPE bot_unknown_query_ratio gauge")?;
+        let total = answered + categories + unknown;
+        writeln!(out, "bot_unknown_query_ratio {}", if total == 0 { 0.0 } else { unknown as f64 / total as f64 })?;
+
+        writeln!(out, "# HELP bot_knowledge_nodes Узлы дерева базы знаний")?;
+        writeln!(out, "# TYPE bot_knowledge_nodes gauge")?;
+        writeln!(out, "bot_knowledge_nodes {}", knowledge.load().tree().len())?;
+        writeln!(out, "# HELP bot_knowledge_loaded_timestamp_seconds Когда база знаний загружена в последний раз")?;
+        writeln!(out, "# TYPE bot_knowledge_loaded_timestamp_seconds gauge")?;
+        writeln!(out, "bot_knowledge_loaded_timestamp_seconds {}", self.last_reload.load(Ordering::Relaxed))?;
+        writeln!(out, "# HELP bot_last_poll_timestamp_seconds Когда getUpdates в последний раз удался")?;
+        writeln!(out, "# TYPE bot_last_poll_timestamp_seconds gauge")?;
+        writeln!(out, "bot_last_poll_timestamp_seconds {}", self.last_poll())
+    }
+}
+
+/// HTTP-сервер с `/metrics` для Prometheus и `/healthz` для проверок живости
+pub struct MetricsServer {
+    server: Server,
+    knowledge: KnowledgeBase,
+    /// Сколько getUpdates может не удаваться, прежде чем бот считается
+    /// зависшим; `None` в режиме вебхука, где опроса нет
+    stall_after: Option<Duration>,
+    started: u64,
+}
+
+impl MetricsServer {
+    pub fn bind(listen: &str, knowledge: KnowledgeBase, stall_after: Option<Duration>) -> Result<Self, Box<dyn Error>> {
+        let server = Server::http(listen).map_err(|error| error as Box<dyn Error>)?;
+
+        Ok(MetricsServer { server, knowledge, stall_after, started: unix_now() })
+    }
+
+    /// Отвечает на запросы в отдельном потоке
+    pub fn spawn(self) {
+        thread::spawn(move || {
+            for request in self.server.incoming_requests() {
+                self.respond(request);
+            }
+        });
+    }
+
+    fn respond(&self, request: Request) {
+        let response = match request.url() {
+            "/metrics" => {
+                let header = Header::from_bytes("Content-Type", "text/plain; version=0.0.4; charset=utf-8").unwrap();
+                Response::from_string(METRICS.render(&self.knowledge)).with_header(header)
+            }
+            "/healthz" => match self.stalled_for() {
+                None => Response::from_string("ok"),
+                Some(seconds) => Response::from_string(format!("getUpdates не удаётся уже {seconds} с")).with_status_code(503),
+            },
+            _ => Response::from_string("not found").with_status_code(404),
+        };
+
+        check_result(request.respond(response), non_fatal);
+    }
+
+    /// Сколько секунд опрос не продвигается, если дольше допустимого
+    fn stalled_for(&self) -> Option<u64> {
+        let stall_after = self.stall_after?.as_secs();
+        // До первого опроса отсчитываем от запуска
+        let since = unix_now().saturating_sub(METRICS.last_poll().max(self.started));
+
+        (since > stall_after).then_some(since)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs())
+}
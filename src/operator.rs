@@ -0,0 +1,173 @@
+use crate::config::{fill, Config};
+use crate::conversation::{ButtonPress, Conversation, InlineButton, Keyboard, Reply, TextMessage};
+use crate::db::Ticket;
+use crate::util::logging::non_fatal;
+
+/// Данные кнопки "Связаться с оператором" под сообщением бота
+pub const OPERATOR_BUTTON: &str = "operator";
+
+/// Кнопка в чате операторов присылает `ticket:close:<номер обращения>`
+const CLOSE_PREFIX: &str = "ticket:close:";
+
+/// Разговоры пользователей с живыми операторами. Пока обращение открыто,
+/// сообщения пользователя пересылаются в группу операторов, а ответы
+/// операторов на них - пользователю
+#[derive(Debug, Default)]
+pub struct Operators {
+    /// Группа операторов; без неё позвать оператора нельзя
+    chat: Option<i64>,
+}
+
+impl Operators {
+    pub fn new(config: &Config) -> Self {
+        Operators { chat: config.operator_chat }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.chat.is_some()
+    }
+
+    /// Сообщения операторов и пользователей, разговаривающих с оператором;
+    /// `None`, если сообщение нужно обработать как обычно
+    pub fn handle(&self, conversation: &Conversation, message: &TextMessage) -> Option<Vec<Reply>> {
+        if message.is_command && message.text == "/operator" {
+            return Some(self.open(conversation, message.chat_id, message.user_id, message.username.as_deref(), None));
+        }
+        let operator_chat = self.chat?;
+        if message.chat_id == operator_chat {
+            return self.operator_message(conversation, operator_chat, message);
+        }
+
+        let ticket = conversation.question_db().open_ticket_in(message.chat_id).map_err(non_fatal).ok()??;
+        if message.text == conversation.messages().operator_close || message.text == "/close" {
+            return Some(self.close(conversation, &ticket, &conversation.messages().operator_closed_by_user));
+        }
+        // Остальные команды работают как обычно
+        if message.is_command {
+            return None;
+        }
+
+        let text = fill(&conversation.messages().operator_ticket_message,
+                        &[("id", &ticket.id), ("author", &author(&ticket)), ("text", &message.text)]);
+        Some(vec![relay(&ticket, operator_chat, text, None)])
+    }
+
+    /// Открывает обращение. `question` - сообщение, с которым пользователь пришёл
+    pub fn open(&self, conversation: &Conversation, chat_id: i64, user_id: u64, username: Option<&str>, question: Option<&str>) -> Vec<Reply> {
+        let messages = conversation.messages();
+        let unavailable = || vec![text(chat_id, messages.operator_unavailable.clone())];
+        let Some(operator_chat) = self.chat else {
+            return unavailable();
+        };
+        let connected = Reply::Message {
+            chat_id,
+            text: messages.operator_connected.clone(),
+            keyboard: Some(Keyboard::Choices(vec![messages.operator_close.clone()])),
+        };
+
+        let db = conversation.question_db();
+        match db.open_ticket_in(chat_id) {
+            Ok(Some(_)) => return vec![connected],
+            Ok(None) => (),
+            Err(error) => {
+                non_fatal(error);
+                return unavailable();
+            }
+        }
+        let ticket = match db.open_ticket(chat_id, user_id, username).and_then(|id| db.ticket(id)) {
+            Ok(Some(ticket)) => ticket,
+            Ok(None) => return unavailable(),
+            Err(error) => {
+                non_fatal(error);
+                return unavailable();
+            }
+        };
+
+        let mut header = fill(&messages.operator_ticket, &[("id", &ticket.id), ("author", &author(&ticket))]);
+        if let Some(question) = question {
+            header.push_str(&format!("\n{question}"));
+        }
+        let close = InlineButton { text: messages.button_close_ticket.clone(), data: format!("{CLOSE_PREFIX}{}", ticket.id) };
+
+        vec![relay(&ticket, operator_chat, header, Some(Keyboard::Inline(vec![close]))), connected]
+    }
+
+    /// Кнопка закрытия обращения в чате операторов; `None`, если это другая кнопка
+    pub fn handle_button(&self, conversation: &Conversation, press: &ButtonPress) -> Option<Vec<Reply>> {
+        let id = press.data.strip_prefix(CLOSE_PREFIX)?;
+        let messages = conversation.messages();
+        let notify = |text: String| Reply::Notify { press_id: press.id.clone(), text };
+        if self.chat != Some(press.chat_id) {
+            return Some(vec![notify(messages.operator_bad_button.clone())]);
+        }
+
+        let ticket = match id.parse::<i64>().map(|id| conversation.question_db().ticket(id)) {
+            Ok(Ok(Some(ticket))) => ticket,
+            Ok(Err(error)) => return Some(vec![notify(fill(&messages.operator_error, &[("error", &error)]))]),
+            _ => return Some(vec![notify(fill(&messages.operator_not_found, &[("id", &id)]))]),
+        };
+        if !ticket.is_open() {
+            return Some(vec![notify(fill(&messages.operator_already_closed, &[("id", &ticket.id)]))]);
+        }
+
+        let mut replies = vec![notify(String::new())];
+        replies.extend(press.message_id.map(|message_id| Reply::RemoveButtons { chat_id: press.chat_id, message_id }));
+        replies.extend(self.close(conversation, &ticket, &messages.operator_closed_by_operator));
+        Some(replies)
+    }
+
+    /// Ответы операторов на пересланные сообщения уходят пользователю,
+    /// `/close` в ответ закрывает обращение
+    fn operator_message(&self, conversation: &Conversation, operator_chat: i64, message: &TextMessage) -> Option<Vec<Reply>> {
+        let db = conversation.question_db();
+        let messages = conversation.messages();
+        let ticket = message.reply_to
+                            .and_then(|replied| db.relayed_ticket(operator_chat, replied).map_err(non_fatal).ok()?)
+                            .and_then(|id| db.ticket(id).map_err(non_fatal).ok()?);
+        let Some(ticket) = ticket else {
+            // Переписка операторов между собой боту не адресована
+            return (!message.is_command).then(Vec::new);
+        };
+
+        if !ticket.is_open() {
+            return Some(vec![text(operator_chat, fill(&messages.operator_already_closed, &[("id", &ticket.id)]))]);
+        }
+        if message.is_command {
+            return (message.text == "/close").then(|| self.close(conversation, &ticket, &messages.operator_closed_by_operator));
+        }
+
+        Some(vec![text(ticket.chat_id, messages.operator_reply.replace("{text}", &message.text))])
+    }
+
+    /// Закрывает обращение и сообщает об этом обеим сторонам;
+    /// `closed` - шаблон сообщения операторам
+    fn close(&self, conversation: &Conversation, ticket: &Ticket, closed: &str) -> Vec<Reply> {
+        if let Err(error) = conversation.question_db().close_ticket(ticket.id) {
+            non_fatal(error);
+        }
+
+        let mut replies = vec![Reply::Message {
+            chat_id: ticket.chat_id,
+            text: conversation.messages().operator_closed.clone(),
+            keyboard: conversation.knowledge().load().get_children(None).ok().map(Keyboard::Choices),
+        }];
+        replies.extend(self.chat.map(|chat| text(chat, fill(closed, &[("id", &ticket.id)]))));
+        replies
+    }
+}
+
+/// Сообщение в чат операторов, ответ на которое попадёт в обращение
+fn relay(ticket: &Ticket, chat_id: i64, text: String, keyboard: Option<Keyboard>) -> Reply {
+    Reply::Relay { ticket_id: ticket.id, chat_id, text, keyboard }
+}
+
+fn author(ticket: &Ticket) -> String {
+    match &ticket.username {
+        Some(username) => format!("@{username}"),
+        None => format!("id {}", ticket.user_id),
+    }
+}
+
+fn text(chat_id: i64, text: String) -> Reply {
+    Reply::Message { chat_id, text, keyboard: None }
+}
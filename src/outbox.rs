@@ -0,0 +1,364 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use frankenstein::{Api, AsyncApi, AsyncTelegramApi, FileUpload, InputFile, SendDocumentParams, TelegramApi};
+use tokio::runtime::Handle;
+
+use crate::metrics::METRICS;
+use crate::polling::retry_after;
+use crate::telegram::Action;
+use crate::util::logging::{check_result, info, non_fatal};
+
+/// Ограничения Telegram на исходящие сообщения
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    /// Не чаще одного сообщения в чат за этот интервал
+    pub per_chat: Duration,
+    /// Не больше стольких сообщений в секунду на всех
+    pub global_per_second: usize,
+    /// Сколько раз повторять вызов при временной ошибке
+    pub max_attempts: u32,
+    /// Задержка перед повтором, если Telegram не сказал, сколько ждать
+    pub retry_delay: Duration,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            per_chat: Duration::from_secs(1),
+            global_per_second: 30,
+            max_attempts: 5,
+            retry_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Чем выполняются вызовы из очереди
+pub enum Executor {
+    /// По одному в потоке очереди
+    Blocking(Api),
+    /// Каждый вызов - задача tokio: медленный вызов одного чата не задерживает остальные
+    Async { api: AsyncApi, runtime: Handle },
+}
+
+/// Сообщает, выполнен ли вызов: номер отправленного сообщения (если вызов
+/// отправлял сообщение) или `Err` с текстом ошибки, если от вызова
+/// окончательно отказались
+pub type Receipt = Box<dyn FnOnce(Result<Option<i32>, String>) + Send>;
+
+type CallResult = Result<Option<i32>, frankenstein::Error>;
+
+enum Message {
+    Queued(Option<i64>, Action, Option<Receipt>),
+    /// Асинхронный вызов чата завершился
+    Done(i64, Pending, CallResult),
+}
+
+/// Очередь исходящих вызовов Bot API. Вызовы одного чата выполняются по
+/// порядку, сообщения отправляются с учётом ограничений Telegram, временные
+/// ошибки повторяются. О вызовах, от которых пришлось отказаться, узнаёт
+/// их `Receipt`; остальные только попадают в лог и метрики
+pub struct Outbox {
+    sender: Mutex<Sender<Message>>,
+}
+
+impl Outbox {
+    /// Запускает поток, распределяющий вызовы из очереди
+    pub fn start(executor: Executor, limits: RateLimits) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        let mut worker = OutboxWorker {
+            executor,
+            limits,
+            receiver,
+            done: sender.clone(),
+            queues: HashMap::new(),
+            order: VecDeque::new(),
+            in_flight: HashSet::new(),
+            ready_at: HashMap::new(),
+            recent_sends: VecDeque::new(),
+        };
+        thread::spawn(move || worker.run());
+
+        Outbox { sender: Mutex::new(sender) }
+    }
+
+    /// Ставит вызовы в очередь чата `chat_id`
+    pub fn enqueue(&self, chat_id: Option<i64>, actions: Vec<Action>) {
+        let sender = self.sender.lock().unwrap();
+        for action in actions {
+            if let Err(error) = sender.send(Message::Queued(chat_id, action, None)) {
+                non_fatal(error);
+            }
+        }
+    }
+
+    /// Ставит вызов в очередь; `receipt` узнает, чем он закончился
+    pub fn enqueue_with_receipt(&self, chat_id: Option<i64>, action: Action, receipt: Receipt) {
+        if let Err(error) = self.sender.lock().unwrap().send(Message::Queued(chat_id, action, Some(receipt))) {
+            non_fatal(error);
+        }
+    }
+}
+
+struct Pending {
+    action: Action,
+    attempts: u32,
+    /// Не повторять раньше этого времени
+    retry_at: Option<Instant>,
+    receipt: Option<Receipt>,
+}
+
+// Вызовы без чата стоят в общей очереди
+const NO_CHAT: i64 = i64::MIN;
+
+struct OutboxWorker {
+    executor: Executor,
+    limits: RateLimits,
+    receiver: Receiver<Message>,
+    /// Куда асинхронные вызовы сообщают о завершении
+    done: Sender<Message>,
+    queues: HashMap<i64, VecDeque<Pending>>,
+    /// Очерёдность чатов, чтобы один чат не занимал всю пропускную способность
+    order: VecDeque<i64>,
+    /// Чаты, чей вызов ещё выполняется: следующий ждёт его, чтобы не нарушить порядок
+    in_flight: HashSet<i64>,
+    /// Когда в чат снова можно писать
+    ready_at: HashMap<i64, Instant>,
+    /// Время отправки сообщений за последнюю секунду
+    recent_sends: VecDeque<Instant>,
+}
+
+impl OutboxWorker {
+    fn run(&mut self) {
+        loop {
+            // Пока очередь пуста, просто ждём новых вызовов
+            if self.order.is_empty() {
+                match self.receiver.recv() {
+                    Ok(message) => self.receive(message),
+                    Err(_) => return,
+                }
+            }
+            while let Ok(message) = self.receiver.try_recv() {
+                self.receive(message);
+            }
+
+            match self.next_ready(Instant::now()) {
+                Ok(chat_id) => self.deliver(chat_id),
+                Err(wait) => match self.receiver.recv_timeout(wait) {
+                    Ok(message) => self.receive(message),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) if self.order.is_empty() => return,
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(wait),
+                },
+            }
+        }
+    }
+
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Queued(chat_id, action, receipt) => self.push(chat_id, action, receipt),
+            Message::Done(chat_id, pending, result) => self.complete(chat_id, pending, result),
+        }
+    }
+
+    fn push(&mut self, chat_id: Option<i64>, action: Action, receipt: Option<Receipt>) {
+        let chat_id = chat_id.unwrap_or(NO_CHAT);
+        let queue = self.queues.entry(chat_id).or_default();
+        if queue.is_empty() && !self.in_flight.contains(&chat_id) {
+            self.order.push_back(chat_id);
+        }
+        queue.push_back(Pending { action, attempts: 0, retry_at: None, receipt });
+    }
+
+    /// Первый чат, чей вызов можно выполнить сейчас, или сколько ждать до ближайшего
+    fn next_ready(&mut self, now: Instant) -> Result<i64, Duration> {
+        while self.recent_sends.front().is_some_and(|&sent| now.duration_since(sent) >= Duration::from_secs(1)) {
+            self.recent_sends.pop_front();
+        }
+        let global_wait = match self.recent_sends.front() {
+            Some(&oldest) if self.recent_sends.len() >= self.limits.global_per_second
+                => Some(Duration::from_secs(1) - now.duration_since(oldest)),
+            _   => None,
+        };
+
+        let mut wait = Duration::MAX;
+        for &chat_id in &self.order {
+            let Some(pending) = self.queues.get(&chat_id).and_then(VecDeque::front) else {
+                continue;
+            };
+            let retry_wait = pending.retry_at.map_or(Duration::ZERO, |retry| retry.saturating_duration_since(now));
+
+            // Ограничения распространяются только на сообщения
+            let chat_wait = if pending.action.is_message() {
+                let chat_wait = self.ready_at
+                                    .get(&chat_id)
+                                    .map_or(Duration::ZERO, |&ready| ready.saturating_duration_since(now));
+                retry_wait.max(chat_wait).max(global_wait.unwrap_or(Duration::ZERO))
+            } else {
+                retry_wait
+            };
+
+            if chat_wait.is_zero() {
+                return Ok(chat_id);
+            }
+            wait = wait.min(chat_wait);
+        }
+
+        Err(wait)
+    }
+
+    fn deliver(&mut self, chat_id: i64) {
+        let Some(pending) = self.queues.get_mut(&chat_id).and_then(VecDeque::pop_front) else {
+            return;
+        };
+
+        let now = Instant::now();
+        if pending.action.is_message() {
+            self.recent_sends.push_back(now);
+            self.ready_at.insert(chat_id, now + self.limits.per_chat);
+        }
+        // Прошедшие отметки больше ни на что не влияют
+        self.ready_at.retain(|_, ready| *ready > now);
+
+        // Пока вызов выполняется, чат не участвует в очерёдности
+        self.order.retain(|&id| id != chat_id);
+        self.in_flight.insert(chat_id);
+
+        match &self.executor {
+            Executor::Blocking(api) => {
+                let result = execute(api, &pending.action);
+                self.complete(chat_id, pending, result);
+            }
+            Executor::Async { api, runtime } => {
+                let (api, done) = (api.clone(), self.done.clone());
+                runtime.spawn(async move {
+                    let result = execute_async(&api, &pending.action).await;
+                    // Очередь живёт, пока жив бот
+                    let _ = done.send(Message::Done(chat_id, pending, result));
+                });
+            }
+        }
+    }
+
+    /// Разбирает результат вызова: повторяет временные ошибки и сообщает итог в `Receipt`
+    fn complete(&mut self, chat_id: i64, mut pending: Pending, result: CallResult) {
+        METRICS.api_call(pending.action.method(), result.is_ok());
+        match result {
+            Err(error) => {
+                pending.attempts += 1;
+                match classify(&error) {
+                    Failure::Transient(delay) if pending.attempts < self.limits.max_attempts => {
+                        let delay = delay.unwrap_or(self.limits.retry_delay);
+                        info(&format!("Временная ошибка Bot API, повтор через {delay:?}: {error}"));
+                        pending.retry_at = Some(Instant::now() + delay);
+                        self.queues.entry(chat_id).or_default().push_front(pending);
+                    }
+                    _ => {
+                        non_fatal(&error);
+                        if let Some(receipt) = pending.receipt {
+                            receipt(Err(error.to_string()));
+                        }
+                    }
+                }
+            }
+            Ok(message_id) => {
+                if let Some(receipt) = pending.receipt {
+                    receipt(Ok(message_id));
+                }
+            }
+        }
+
+        // Чат уходит в конец очерёдности, пустые чаты забываем
+        self.in_flight.remove(&chat_id);
+        if self.queues.get(&chat_id).is_some_and(|queue| !queue.is_empty()) {
+            self.order.push_back(chat_id);
+        } else {
+            self.queues.remove(&chat_id);
+        }
+    }
+}
+
+/// Выполняет вызов; для сообщений возвращает номер отправленного
+fn execute(api: &Api, action: &Action) -> CallResult {
+    match action {
+        Action::SendMessage(params) => return Ok(Some(api.send_message(params)?.result.message_id)),
+        Action::AnswerCallbackQuery(params) => { api.answer_callback_query(params)?; }
+        Action::EditMessageReplyMarkup(params) => { api.edit_message_reply_markup(params)?; }
+        Action::SendDocument { chat_id, file_name, content } => {
+            let upload = Upload::write(file_name, content)?;
+            return Ok(Some(api.send_document(&upload.params(*chat_id))?.result.message_id));
+        }
+    }
+
+    Ok(None)
+}
+
+/// То же, что `execute`, асинхронным клиентом
+async fn execute_async(api: &AsyncApi, action: &Action) -> CallResult {
+    match action {
+        Action::SendMessage(params) => return Ok(Some(api.send_message(params).await?.result.message_id)),
+        Action::AnswerCallbackQuery(params) => { api.answer_callback_query(params).await?; }
+        Action::EditMessageReplyMarkup(params) => { api.edit_message_reply_markup(params).await?; }
+        Action::SendDocument { chat_id, file_name, content } => {
+            let upload = Upload::write(file_name, content)?;
+            return Ok(Some(api.send_document(&upload.params(*chat_id)).await?.result.message_id));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Bot API загружает файлы только с диска: содержимое пишется во
+/// временный каталог, который удаляется вместе со значением
+struct Upload {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl Upload {
+    fn write(file_name: &str, content: &[u8]) -> Result<Self, frankenstein::Error> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("mr-deeds-upload-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::SeqCst)));
+        let path = dir.join(file_name);
+        fs::create_dir_all(&dir).and_then(|()| fs::write(&path, content))
+                                .map_err(|error| frankenstein::Error::Encode(error.to_string()))?;
+
+        Ok(Upload { dir, path })
+    }
+
+    fn params(&self, chat_id: i64) -> SendDocumentParams {
+        SendDocumentParams::builder().chat_id(chat_id).document(FileUpload::InputFile(InputFile { path: self.path.clone() })).build()
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        check_result(fs::remove_dir_all(&self.dir), non_fatal);
+    }
+}
+
+enum Failure {
+    /// Стоит повторить, возможно, через указанное Telegram время
+    Transient(Option<Duration>),
+    /// Повтор не поможет: например, пользователь заблокировал бота
+    Permanent,
+}
+
+fn classify(error: &frankenstein::Error) -> Failure {
+    match error {
+        frankenstein::Error::Api(response) if response.error_code == 429 || response.error_code >= 500
+            => Failure::Transient(retry_after(error)),
+        frankenstein::Error::Api(_) => Failure::Permanent,
+        // Сетевые ошибки и ответы, которые не удалось разобрать
+        frankenstein::Error::Http(http) if http.code == 429 || http.code >= 500
+            => Failure::Transient(None),
+        _ => Failure::Permanent,
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use frankenstein::{AllowedUpdate, GetUpdatesParams};
+
+/// Параметры long polling
+#[derive(Debug, Clone)]
+pub struct PollingConfig {
+    /// Сколько секунд Telegram держит запрос getUpdates, если обновлений нет
+    pub timeout: u32,
+    /// Какие обновления присылать; остальные бот всё равно не обрабатывает
+    pub allowed_updates: Vec<AllowedUpdate>,
+    /// Первая задержка после ошибки; дальше она удваивается до `backoff_max`
+    pub backoff_initial: Duration,
+    pub backoff_max: Duration,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        PollingConfig {
+            timeout: 30,
+            allowed_updates: vec![AllowedUpdate::Message, AllowedUpdate::CallbackQuery],
+            backoff_initial: Duration::from_secs(1),
+            backoff_max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl PollingConfig {
+    /// Параметры getUpdates, начиная с обновления `offset`
+    pub fn params(&self, offset: Option<i64>) -> GetUpdatesParams {
+        GetUpdatesParams {
+            offset,
+            limit: None,
+            timeout: Some(self.timeout),
+            allowed_updates: Some(self.allowed_updates.clone()),
+        }
+    }
+}
+
+/// Экспоненциальная задержка между повторами после ошибок
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(config: &PollingConfig) -> Self {
+        Backoff { initial: config.backoff_initial, max: config.backoff_max, attempt: 0 }
+    }
+
+    /// Запрос прошёл успешно - следующая ошибка снова начнёт с минимальной задержки
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Задержка перед следующим запросом после ошибки. Если Telegram
+    /// сообщил, сколько ждать (429 Too Many Requests), ждём столько
+    pub fn delay(&mut self, error: &frankenstein::Error) -> Duration {
+        if let Some(retry_after) = retry_after(error) {
+            return retry_after;
+        }
+
+        let ceiling = self.initial
+                          .saturating_mul(2u32.saturating_pow(self.attempt))
+                          .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        // Случайная задержка от половины до полной, чтобы после сбоя
+        // запросы не шли одновременно
+        let half = ceiling / 2;
+        half + Duration::from_millis(random_below(half.as_millis() as u64 + 1))
+    }
+}
+
+/// Сколько Telegram просит подождать перед повтором
+pub fn retry_after(error: &frankenstein::Error) -> Option<Duration> {
+    match error {
+        frankenstein::Error::Api(response) => response.parameters
+                                                      .as_ref()
+                                                      .and_then(|parameters| parameters.retry_after)
+                                                      .map(|seconds| Duration::from_secs(seconds.into())),
+        _ => None,
+    }
+}
+
+/// Случайное число в `[0, bound)`. Криптостойкость не нужна, поэтому
+/// обходимся случайно инициализированным хешером из стандартной библиотеки
+fn random_below(bound: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(bound);
+    hasher.finish() % bound.max(1)
+}
@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use crate::config::{fill, Messages};
+use crate::db::{Database, EventKind, QuestionStatus};
+
+/// Сколько строк в каждом топе отчёта
+const TOP: usize = 10;
+
+/// Сводка работы бота за период, для /stats и `mr-deeds stats`
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Первый и последний день периода, `ГГГГ-ММ-ДД`
+    pub from: String,
+    pub to: String,
+    /// Пользователи по дням; дни без пользователей пропущены
+    pub daily_users: Vec<(String, usize)>,
+    pub messages: usize,
+    pub top_questions: Vec<(String, usize)>,
+    pub top_categories: Vec<(String, usize)>,
+    /// Запросы, которые бот не понял
+    pub unmatched: Vec<(String, usize)>,
+    /// Вопросы на модерации сейчас, а не за период
+    pub pending: usize,
+    /// Среднее время ответа на сохранённый вопрос, секунды
+    pub mean_answer_time: Option<f64>,
+}
+
+impl Report {
+    /// Отчёт с `from` по `to` включительно
+    pub fn build(db: &Database, from: &str, to: &str) -> rusqlite::Result<Self> {
+        Ok(Report {
+            from: from.to_string(),
+            to: to.to_string(),
+            daily_users: db.daily_users(from, to)?,
+            messages: db.count_messages(from, to)?,
+            top_questions: db.top_labels(EventKind::QuestionAnswered, from, to, TOP)?,
+            top_categories: db.top_labels(EventKind::CategoryOpened, from, to, TOP)?,
+            unmatched: db.top_labels(EventKind::UnknownQuery, from, to, TOP)?,
+            pending: db.count_with_status(QuestionStatus::OPEN)?,
+            mean_answer_time: db.mean_answer_time(from, to)?,
+        })
+    }
+
+    /// Отчёт за `days` последних дней
+    pub fn last_days(db: &Database, days: u32) -> rusqlite::Result<Self> {
+        let (from, to) = db.period(None, days)?;
+        Self::build(db, &from, &to)
+    }
+
+    /// Имя файла для выгрузки отчёта в CSV
+    pub fn csv_name(&self) -> String {
+        format!("stats-{}-{}.csv", self.from, self.to)
+    }
+
+    /// Отчёт текстом для сообщения или терминала
+    pub fn render(&self, messages: &Messages) -> String {
+        let mut text = fill(&messages.report_title, &[("from", &self.from), ("to", &self.to)]);
+
+        push_list(&mut text, &messages.report_daily_users, &self.daily_users, messages);
+        text.push_str(&format!("\n{}", fill(&messages.report_messages, &[("count", &self.messages)])));
+        push_list(&mut text, &messages.report_top_questions, &self.top_questions, messages);
+        push_list(&mut text, &messages.report_top_categories, &self.top_categories, messages);
+        push_list(&mut text, &messages.report_unmatched, &self.unmatched, messages);
+        text.push_str(&format!("\n{}", fill(&messages.report_pending, &[("count", &self.pending)])));
+        let answer_time = self.mean_answer_time.map_or(messages.report_no_answers.clone(), |seconds| duration(seconds, messages));
+        text.push_str(&format!("\n{}", fill(&messages.report_answer_time, &[("time", &answer_time)])));
+
+        text
+    }
+
+    /// Отчёт таблицей `section,label,value`
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+
+        writeln!(out, "section,label,value")?;
+        for (day, users) in &self.daily_users {
+            writeln!(out, "daily_users,{day},{users}")?;
+        }
+        writeln!(out, "messages,,{}", self.messages)?;
+        for (section, rows) in [("top_question", &self.top_questions), ("top_category", &self.top_categories), ("unmatched", &self.unmatched)] {
+            for (label, count) in rows {
+                writeln!(out, "{section},{},{count}", quote(label))?;
+            }
+        }
+        writeln!(out, "pending,,{}", self.pending)?;
+        if let Some(seconds) = self.mean_answer_time {
+            writeln!(out, "mean_answer_seconds,,{seconds:.0}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Заголовок и строки одного топа отчёта
+fn push_list(text: &mut String, title: &str, rows: &[(String, usize)], messages: &Messages) {
+    text.push('\n');
+    text.push_str(title);
+    if rows.is_empty() {
+        text.push_str(&messages.report_empty);
+    }
+    for (label, count) in rows {
+        text.push_str(&format!("\n  {label} - {count}"));
+    }
+}
+
+/// Длительность вида "2 ч 5 мин"; меньше минуты - в секундах
+fn duration(seconds: f64, messages: &Messages) -> String {
+    let seconds = seconds.round() as u64;
+    match (seconds / 3600, seconds % 3600 / 60) {
+        (0, 0) => fill(&messages.report_seconds, &[("seconds", &seconds)]),
+        (0, minutes) => fill(&messages.report_minutes, &[("minutes", &minutes)]),
+        (hours, minutes) => fill(&messages.report_hours, &[("hours", &hours), ("minutes", &minutes)]),
+    }
+}
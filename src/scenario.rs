@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::conversation::{ButtonPress, Conversation, Event, InlineButton, Keyboard, Reply, TextMessage};
+use crate::error::scenario::ScenarioError;
+
+// Сценарий изображает один личный чат
+const CHAT_ID: i64 = 1;
+const USER_ID: u64 = 1;
+
+/// Шаг сценария: что делает пользователь и чего ждём от бота.
+/// Непроверяемые поля можно не указывать
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Пользователь пишет текст (или команду)
+    pub say: Option<String>,
+    /// Пользователь нажимает кнопку с таким текстом
+    pub press: Option<String>,
+    /// Текст одного из сообщений бота в ответ на шаг: за ответом
+    /// может идти, например, вопрос "Ответ помог?"
+    pub reply: Option<String>,
+    /// Клавиатура, которая после шага у пользователя
+    pub keyboard: Option<Vec<String>>,
+    /// Кнопки под последним сообщением бота
+    pub buttons: Option<Vec<String>>,
+    /// Уведомление в ответ на нажатие кнопки
+    pub notify: Option<String>,
+}
+
+/// Сценарий разговора с ботом из TOML-файла:
+///
+/// ```toml
+/// name = "Сроки практики"
+///
+/// [[step]]
+/// say = "/start"
+/// keyboard = ["Учебный процесс"]
+///
+/// [[step]]
+/// say = "Какие сроки сдачи практики?"
+/// reply = "1 месяц"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// По умолчанию - имя файла
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "step")]
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, ScenarioError> {
+        let error = |problem: String| ScenarioError { path: path.to_string(), problem };
+
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let mut scenario: Scenario = toml::from_str(&text).map_err(|e| error(e.to_string()))?;
+
+        for (number, step) in scenario.steps.iter().enumerate() {
+            if step.say.is_some() == step.press.is_some() {
+                return Err(error(format!("шаг {}: нужно указать ровно одно из say и press", number + 1)));
+            }
+        }
+        if scenario.name.is_empty() {
+            scenario.name = Path::new(path).file_stem().map_or(path.into(), |stem| stem.to_string_lossy().into());
+        }
+
+        Ok(scenario)
+    }
+
+    /// Проходит сценарий с ботом и возвращает расхождения с ожидаемым.
+    /// Если нажать кнопку не удалось, дальше сценарий не выполняется
+    pub fn run(&self, conversation: &Conversation) -> Vec<String> {
+        let mut chat = Chat::default();
+        let mut mismatches = vec![];
+
+        for (number, step) in self.steps.iter().enumerate() {
+            let action = step.say.as_ref().or(step.press.as_ref()).map_or("", String::as_str);
+            let mut mismatch = |problem: String| mismatches.push(format!("шаг {} ({action:?}): {problem}", number + 1));
+
+            let event = match (&step.say, &step.press) {
+                (Some(text), _) => chat.text(text.clone()),
+                (None, Some(label)) => match chat.press(label) {
+                    Some(event) => event,
+                    None => {
+                        mismatch(format!("нет кнопки {label:?}, есть: {:?}", chat.labels()));
+                        break;
+                    }
+                },
+                (None, None) => continue,
+            };
+
+            let replies = conversation.handle(event);
+            let mut texts = vec![];
+            let mut notify = None;
+            for reply in replies {
+                match reply {
+                    Reply::Message { text, keyboard, .. } => {
+                        chat.show(keyboard);
+                        texts.push(text);
+                    }
+                    Reply::Notify { text, .. } => notify = Some(text),
+                    Reply::RemoveButtons { message_id, .. } => chat.inline.retain(|(id, _)| *id != message_id),
+                    // Уходит в чужой чат
+                    Reply::Answer { .. } | Reply::Forward { .. } | Reply::Relay { .. } => (),
+                    // Пользователям бот файлов не шлёт
+                    Reply::Document { .. } => (),
+                }
+            }
+
+            if let Some(expected) = &step.reply {
+                if !texts.contains(expected) {
+                    let received = if texts.is_empty() { "ничего".to_string() } else { format!("{texts:?}") };
+                    mismatch(format!("ожидался ответ {expected:?}, получено {received}"));
+                }
+            }
+            if let Some(expected) = &step.keyboard {
+                if &chat.choices != expected {
+                    mismatch(format!("ожидалась клавиатура {expected:?}, получена {:?}", chat.choices));
+                }
+            }
+            if let Some(expected) = &step.buttons {
+                let buttons: Vec<&str> = chat.inline.iter().map(|(_, button)| button.text.as_str()).collect();
+                if &buttons != expected {
+                    mismatch(format!("ожидались кнопки {expected:?}, получены {buttons:?}"));
+                }
+            }
+            if let Some(expected) = &step.notify {
+                if notify.as_ref() != Some(expected) {
+                    mismatch(format!("ожидалось уведомление {expected:?}, получено {}", shown(&notify)));
+                }
+            }
+        }
+
+        mismatches
+    }
+}
+
+fn shown(text: &Option<String>) -> String {
+    text.as_ref().map_or("ничего".to_string(), |text| format!("{text:?}"))
+}
+
+/// Что пользователь видит в чате
+#[derive(Default)]
+struct Chat {
+    message_id: i32,
+    press_id: u32,
+    choices: Vec<String>,
+    /// Кнопки под последним сообщением бота
+    inline: Vec<(i32, InlineButton)>,
+}
+
+impl Chat {
+    fn text(&self, text: String) -> Event {
+        Event::Text(TextMessage { chat_id: CHAT_ID, user_id: USER_ID, username: None, is_command: text.starts_with('/'), reply_to: None, text })
+    }
+
+    /// Нажатие кнопки под сообщением или на клавиатуре
+    fn press(&mut self, label: &str) -> Option<Event> {
+        if let Some((message_id, button)) = self.inline.iter().find(|(_, button)| button.text == label) {
+            self.press_id += 1;
+            return Some(Event::Button(ButtonPress {
+                id: self.press_id.to_string(),
+                chat_id: CHAT_ID,
+                user_id: USER_ID,
+                message_id: Some(*message_id),
+                data: button.data.clone(),
+            }));
+        }
+
+        self.choices.iter().any(|choice| choice == label).then(|| self.text(label.to_string()))
+    }
+
+    fn show(&mut self, keyboard: Option<Keyboard>) {
+        self.message_id += 1;
+        self.inline.clear();
+        match keyboard {
+            Some(Keyboard::Choices(choices)) => self.choices = choices,
+            Some(Keyboard::Inline(buttons)) => {
+                self.inline = buttons.into_iter().map(|button| (self.message_id, button)).collect();
+            }
+            None => (),
+        }
+    }
+
+    fn labels(&self) -> Vec<&str> {
+        self.inline.iter()
+                   .map(|(_, button)| button.text.as_str())
+                   .chain(self.choices.iter().map(String::as_str))
+                   .collect()
+    }
+}
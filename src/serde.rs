@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::{fs::File, path::Path};
 
@@ -6,17 +6,70 @@ use serde_json::Value;
 
 use crate::arenatree::*;
 use crate::error::serde::*;
-use crate::util::logging::{check_result, fatal, info, non_fatal};
+use crate::similarity;
+
+/// Тип ноды базы знаний
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Root,
+    Category,
+    Question,
+}
+
+/// Содержимое ноды базы знаний
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QANode {
+    pub kind: NodeKind,
+    pub label: String,
+    /// Ответ есть только у вопросов
+    pub answer: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl QANode {
+    pub fn new(kind: NodeKind, label: String) -> Self {
+        QANode { kind, label, answer: None, metadata: BTreeMap::new() }
+    }
+
+    pub fn question(label: String, answer: String) -> Self {
+        QANode { answer: Some(answer), ..Self::new(NodeKind::Question, label) }
+    }
+
+    pub fn is_question(&self) -> bool {
+        self.kind == NodeKind::Question
+    }
+}
+
+impl Keyed for QANode {
+    type Key = str;
+
+    fn key(&self) -> &str {
+        &self.label
+    }
+}
 
 pub struct QASerde {
-    tree: Arena<String>,
-    pub question_id: Vec<NodeId> 
+    tree: Arena<QANode>,
+    pub question_id: Vec<NodeId>,
+    /// Поисковый индекс: название ноды -> её индекс в дереве
+    index: HashMap<String, NodeId>,
+}
+
+impl Default for QASerde {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl QASerde {
     pub fn new() -> Self {
 
-        QASerde { tree: Arena::new(), question_id: vec![] }
+        QASerde { tree: Arena::new(), question_id: vec![], index: HashMap::new() }
+    }
+
+    /// Собирает базу из готового дерева и индекса (например, из снапшота)
+    pub fn from_parts(tree: Arena<QANode>, question_id: Vec<NodeId>, index: HashMap<String, NodeId>) -> Self {
+        QASerde { tree, question_id, index }
     }
 
     pub fn build(mut self, path: &str) -> Result<Self, Box<dyn Error>> {
@@ -33,59 +86,222 @@ impl QASerde {
             Err(e)  => return Err(Box::new(e)),
         };
 
-        check_result(self.parse_into_tree(v, None), fatal);
+        self.parse_into_tree(v, None)?;
 
-        self.tree.dbg_list_nodes();
         Ok(self)
-    } 
+    }
+
+    /// Собирает базу из уже разобранного JSON
+    pub fn from_json(value: Value) -> Result<Self, FileFormattingError> {
+        let mut qna = Self::new();
+        qna.parse_into_tree(value, None)?;
+        Ok(qna)
+    }
 
     fn parse_into_tree(&mut self, value: Value, parent: Option<NodeId>) -> Result<(), FileFormattingError> {
         match value {
             Value::Object(map) => {
                 for (node_data, children) in map {
-                    dbg!("Объект");
-                    let new_parent = self.tree.add_node(node_data.to_owned(), parent);
+                    let new_parent = match children {
+                        // Строка - это ответ, значит ключ - вопрос
+                        Value::String(answer) => {
+                            let id = self.add_indexed(QANode::question(node_data, answer), parent);
+                            self.question_id.extend(id);
+                            continue;
+                        }
+                        _ => {
+                            let kind = if parent.is_none() { NodeKind::Root } else { NodeKind::Category };
+                            self.add_indexed(QANode::new(kind, node_data), parent)
+                        }
+                    };
                     self.parse_into_tree(children, new_parent)?;
                 }
             }
-            Value::String(leaf) => {
-                dbg!("Строка");
-                let _ = self.tree.add_node(leaf, parent);
-                self.question_id.push(parent.unwrap_or(0));
-            }
             _ => return Err(FileFormattingError),
         }
 
         Ok(())
     }
 
+    fn add_indexed(&mut self, node: QANode, parent: Option<NodeId>) -> Option<NodeId> {
+        let label = node.label.clone();
+        let id = self.tree.add_node(node, parent)?;
+        // При совпадении названий находится первая нода, как и при обходе дерева
+        self.index.entry(label).or_insert(id);
+        Some(id)
+    }
+
+    pub fn tree(&self) -> &Arena<QANode> {
+        &self.tree
+    }
+
+    pub fn index(&self) -> &HashMap<String, NodeId> {
+        &self.index
+    }
+
+    pub fn find(&self, node_data: &str) -> Option<NodeId> {
+        self.index.get(node_data).copied()
+    }
+
+    pub fn get_node(&self, node_id: NodeId) -> Option<&QANode> {
+        self.tree.get(node_id)
+    }
+
     pub fn is_question(&self, node_data: &str) -> bool {
-        self.tree.get_leaves_parents().contains(&self.tree.get_id_by_value(node_data.to_owned()).unwrap())
+        self.find(node_data)
+            .and_then(|id| self.tree.get(id))
+            .is_some_and(QANode::is_question)
+    }
+
+    pub fn get_answer(&self, node_data: &str) -> Option<&str> {
+        self.find(node_data)
+            .and_then(|id| self.tree.get(id))
+            .and_then(|node| node.answer.as_deref())
     }
 
     pub fn get_parent(&self, node_id: NodeId) -> Option<NodeId> {
         self.tree.get_parent(node_id)
     }
 
+    /// Путь из названий категорий от корня (не включая его) до родителя ноды
+    pub fn get_path(&self, node_id: NodeId) -> Vec<String> {
+        let mut path = vec![];
+        let mut current = self.tree.get_parent(node_id);
+
+        while let Some(id) = current {
+            match self.tree.get(id) {
+                Some(node) if node.kind == NodeKind::Category => path.push(node.label.clone()),
+                _ => (),
+            }
+            current = self.tree.get_parent(id);
+        }
+
+        path.reverse();
+        path
+    }
+
     pub fn get_children(&self, name: Option<String>) -> Result<Vec<String>, IndexError<String>> {
-        let name = name.unwrap_or(self.tree.get_root_value().ok_or(IndexError { index: "root".to_string() })?);
-
-        if let Some(children_ids) = self.tree.get_children_by_value(name.clone()) {
-            Ok(
-                children_ids.iter()
-                            .map(|&id| (self.tree.get(id)
-                                                // TODO некрасиво, что-то сделать
-                                                .unwrap()
-                                                .clone()))
-                            .collect()
-            )
-        } 
-        else {
-            Err(IndexError { index: name.to_owned() })
+        let id = match name {
+            Some(name) => self.find(&name).ok_or(IndexError { index: name })?,
+            None       => self.tree.get_root_id().ok_or(IndexError { index: "root".to_string() })?,
+        };
+
+        let children_ids = self.tree.get_children_by_id(id).ok_or(IndexError { index: id.to_string() })?;
+        Ok(
+            children_ids.iter()
+                        .filter_map(|&id| self.tree.get(id))
+                        .map(|node| node.label.clone())
+                        .collect()
+        )
+    }
+
+    /// Проблемы содержимого, из-за которых бот будет вести себя неожиданно
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        for (id, node) in self.tree.iter() {
+            let location = format!("[{}] \"{}\"", self.get_path(id).join(" / "), node.label);
+
+            // Ноды ищутся по названию, поэтому повторы недостижимы
+            if self.find(&node.label) != Some(id) {
+                problems.push(format!("{location}: название повторяется, эта нода недостижима"));
+            }
+            if node.label.trim().is_empty() {
+                problems.push(format!("{location}: пустое название"));
+            }
+            if node.label.starts_with('/') {
+                problems.push(format!("{location}: название похоже на команду бота"));
+            }
+            match node.kind {
+                NodeKind::Question if node.answer.as_deref().is_none_or(|answer| answer.trim().is_empty())
+                    => problems.push(format!("{location}: пустой ответ")),
+                NodeKind::Category if self.tree.get_children_by_id(id).is_none_or(|children| children.is_empty())
+                    => problems.push(format!("{location}: пустая категория")),
+                _ => (),
+            }
         }
+
+        problems
     }
 
     pub fn contains(&self, node_data: &str) -> bool {
-        self.tree.contains(node_data.to_owned())
+        self.index.contains_key(node_data)
+    }
+
+    /// Вопрос базы знаний, похожий на `text` настолько, что это, видимо, он же
+    pub fn find_similar(&self, text: &str) -> Option<&QANode> {
+        self.question_id
+            .iter()
+            .filter_map(|&id| self.tree.get(id))
+            .map(|node| (node, similarity::similarity(text, &node.label)))
+            .filter(|&(_, score)| score >= similarity::THRESHOLD)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(node, _)| node)
+    }
+
+    /// Находит категорию по пути из названий от корня (пустой путь - корень).
+    /// В отличие от `find`, различает одноимённые категории в разных ветках
+    pub fn find_path(&self, path: &[String]) -> Option<NodeId> {
+        let mut current = self.tree.get_root_id()?;
+        for label in path {
+            current = self.tree
+                          .get_children_by_id(current)?
+                          .into_iter()
+                          .find(|&id| self.tree.get(id).is_some_and(|node| !node.is_question() && &node.label == label))?;
+        }
+        Some(current)
+    }
+
+    /// Названия подкатегорий ноды
+    pub fn get_categories(&self, node_id: NodeId) -> Vec<String> {
+        self.tree
+            .get_children_by_id(node_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| self.tree.get(id))
+            .filter(|node| node.kind == NodeKind::Category)
+            .map(|node| node.label.clone())
+            .collect()
+    }
+
+    /// Добавляет вопрос с ответом в категорию. Вопрос, совпадающий по названию
+    /// с уже существующей нодой, был бы недостижим, поэтому не добавляется
+    pub fn add_question(&mut self, category: NodeId, label: String, answer: String) -> Result<NodeId, IndexError<String>> {
+        if self.contains(&label) || self.tree.get(category).is_none_or(QANode::is_question) {
+            return Err(IndexError { index: label });
+        }
+
+        let id = self.add_indexed(QANode::question(label.clone(), answer), Some(category))
+                     .ok_or(IndexError { index: label })?;
+        self.question_id.push(id);
+        Ok(id)
+    }
+
+    /// Дерево в том же формате JSON, из которого его собирает `build`,
+    /// с ключами в порядке нод
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.tree
+                .iter()
+                .filter(|&(id, _)| self.tree.get_parent(id).is_none())
+                .map(|(id, node)| (node.label.clone(), self.node_to_json(id, node)))
+                .collect()
+        )
+    }
+
+    fn node_to_json(&self, node_id: NodeId, node: &QANode) -> Value {
+        // Ответ - строка, категория - объект с детьми
+        if node.is_question() {
+            return Value::String(node.answer.clone().unwrap_or_default());
+        }
+
+        Value::Object(
+            self.tree
+                .get_children_by_id(node_id)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| self.tree.get(id).map(|child| (child.label.clone(), self.node_to_json(id, child))))
+                .collect()
+        )
     }
 }
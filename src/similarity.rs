@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+/// Тексты с похожестью не ниже этой считаются одним вопросом
+pub const THRESHOLD: f64 = 0.7;
+
+/// Приводит вопрос к виду, в котором сравниваются формулировки:
+/// строчные буквы, без знаков препинания и лишних пробелов, "ё" как "е"
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace('ё', "е")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Похожесть двух текстов от 0 до 1: коэффициент Дайса по триграммам
+/// символов нормализованных текстов
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+
+    let (a, b) = (trigrams(&a), trigrams(&b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let common = a.intersection(&b).count();
+
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Триграммы текста с пробелами по краям, чтобы начала и концы слов тоже сравнивались
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let chars: Vec<char> = format!(" {text} ").chars().collect();
+    chars.windows(3).map(|window| [window[0], window[1], window[2]]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_case_punctuation_and_yo() {
+        assert_eq!(normalize("  Где ЁЛКА,  стоит?!"), "где елка стоит");
+        assert_eq!(normalize("Корпус №2 - где?"), "корпус 2 где");
+        assert_eq!(normalize("?!"), "");
+    }
+
+    #[test]
+    fn same_question_written_differently() {
+        assert_eq!(similarity("Когда сессия?", "когда  СЕССИЯ"), 1.0);
+        assert!(similarity("Когда сдавать практику?", "Когда сдать практику") >= THRESHOLD);
+    }
+
+    #[test]
+    fn different_questions() {
+        assert!(similarity("Где столовая?", "Когда сессия?") < THRESHOLD);
+        assert_eq!(similarity("?", "Где столовая?"), 0.0);
+    }
+
+    /// Вопросы, отличающиеся одним словом, почти одинаковы по триграммам,
+    /// поэтому похожесть годится только для подсказки, а не для ответа
+    #[test]
+    fn near_misses_look_alike() {
+        let pairs = [
+            ("Где находится корпус Б?", "Где находится корпус А?", 0.909),
+            ("Когда начинается сессия у первого курса?", "Когда начинается сессия у второго курса?", 0.846),
+        ];
+        for (a, b, expected) in pairs {
+            let score = similarity(a, b);
+            assert!((score - expected).abs() < 0.001, "{a} / {b}: {score}");
+            assert!(score >= THRESHOLD);
+        }
+    }
+}
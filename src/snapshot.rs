@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+
+use crate::arenatree::{Arena, NodeId};
+use crate::error::snapshot::SnapshotError;
+use crate::serde::{NodeKind, QANode, QASerde};
+use crate::util::logging::{check_result, info, non_fatal};
+
+// Формат снапшота (все числа little-endian):
+//   MAGIC | версия: u16 | crc32 исходного JSON: u32 | crc32 данных: u32 | длина данных: u64 | данные
+// Данные:
+//   число нод: u32, далее для каждой ноды:
+//     тип: u8 | родитель: u32 (NO_PARENT для корня) | название | ответ (флаг u8 + строка) | метаданные
+//   список вопросов: u32 + u32 * n
+//   поисковый индекс: u32 + (строка, u32) * n
+// Строки хранятся как длина u32 + байты UTF-8.
+const MAGIC: &[u8; 4] = b"QNAS";
+pub const VERSION: u16 = 1;
+const NO_PARENT: u32 = u32::MAX;
+const HEADER_LEN: usize = 4 + 2 + 4 + 4 + 8;
+
+/// Загружает базу знаний из снапшота, если он есть и соответствует
+/// JSON-файлу. Иначе собирает базу из JSON и пересобирает снапшот
+pub fn load_or_build(json_path: &str, snapshot_path: &str) -> Result<QASerde, Box<dyn Error>> {
+    let source_crc = crc32fast::hash(&fs::read(json_path)?);
+
+    match load(snapshot_path, Some(source_crc)) {
+        Ok(qna) => {
+            info("База знаний загружена из снапшота");
+            return Ok(qna);
+        }
+        Err(error) => non_fatal(error),
+    }
+
+    let qna = QASerde::new().build(json_path)?;
+    check_result(save(&qna, source_crc, snapshot_path), non_fatal);
+
+    Ok(qna)
+}
+
+/// Собирает снапшот из JSON-файла
+pub fn build(json_path: &str, snapshot_path: &str) -> Result<QASerde, Box<dyn Error>> {
+    let source_crc = crc32fast::hash(&fs::read(json_path)?);
+    let qna = QASerde::new().build(json_path)?;
+    save(&qna, source_crc, snapshot_path)?;
+
+    Ok(qna)
+}
+
+/// Записывает изменённую базу в JSON-файл и пересобирает снапшот. Файл
+/// переписывается целиком: порядок ключей сохраняется, а отступы
+/// становятся стандартными
+pub fn persist(qna: &QASerde, json_path: &str, snapshot_path: &str) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_vec_pretty(&qna.to_json())?;
+
+    let tmp_path = format!("{json_path}.tmp");
+    fs::write(&tmp_path, &json)?;
+    fs::rename(&tmp_path, json_path)?;
+    save(qna, crc32fast::hash(&json), snapshot_path)?;
+
+    Ok(())
+}
+
+pub fn save(qna: &QASerde, source_crc: u32, path: &str) -> Result<(), SnapshotError> {
+    let payload = encode(qna);
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&source_crc.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+
+    // Пишем во временный файл и переименовываем, чтобы не оставить полузаписанный снапшот
+    let tmp_path = format!("{path}.tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Читает снапшот. Если передана контрольная сумма исходного JSON,
+/// снапшот от другой версии файла считается устаревшим
+pub fn load(path: &str, source_crc: Option<u32>) -> Result<QASerde, SnapshotError> {
+    let bytes = fs::read(path)?;
+    let mut reader = Reader { bytes: &bytes, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let snapshot_source_crc = reader.u32()?;
+    if source_crc.is_some_and(|crc| crc != snapshot_source_crc) {
+        return Err(SnapshotError::Stale);
+    }
+    let payload_crc = reader.u32()?;
+    let payload_len = reader.u64()? as usize;
+    let payload = reader.take(payload_len)?;
+    if crc32fast::hash(payload) != payload_crc {
+        return Err(SnapshotError::ChecksumMismatch);
+    }
+    reader.finish()?;
+
+    decode(payload)
+}
+
+fn encode(qna: &QASerde) -> Vec<u8> {
+    let mut out = vec![];
+    let tree = qna.tree();
+
+    put_u32(&mut out, tree.len() as u32);
+    for (id, node) in tree.iter() {
+        out.push(match node.kind {
+            NodeKind::Root     => 0,
+            NodeKind::Category => 1,
+            NodeKind::Question => 2,
+        });
+        put_u32(&mut out, tree.get_parent(id).map_or(NO_PARENT, |p| p as u32));
+        put_str(&mut out, &node.label);
+        match &node.answer {
+            Some(answer) => {
+                out.push(1);
+                put_str(&mut out, answer);
+            }
+            None => out.push(0),
+        }
+        put_u32(&mut out, node.metadata.len() as u32);
+        for (key, value) in &node.metadata {
+            put_str(&mut out, key);
+            put_str(&mut out, value);
+        }
+    }
+
+    put_u32(&mut out, qna.question_id.len() as u32);
+    for &id in &qna.question_id {
+        put_u32(&mut out, id as u32);
+    }
+
+    // Сортируем индекс, чтобы одинаковые базы давали одинаковые снапшоты
+    let mut index: Vec<(&String, &NodeId)> = qna.index().iter().collect();
+    index.sort();
+    put_u32(&mut out, index.len() as u32);
+    for (label, &id) in index {
+        put_str(&mut out, label);
+        put_u32(&mut out, id as u32);
+    }
+
+    out
+}
+
+fn decode(payload: &[u8]) -> Result<QASerde, SnapshotError> {
+    let mut reader = Reader { bytes: payload, pos: 0 };
+    let mut tree = Arena::new();
+
+    let node_count = reader.u32()?;
+    for _ in 0..node_count {
+        let kind = match reader.u8()? {
+            0 => NodeKind::Root,
+            1 => NodeKind::Category,
+            2 => NodeKind::Question,
+            _ => return Err(SnapshotError::Corrupted),
+        };
+        let parent = match reader.u32()? {
+            NO_PARENT => None,
+            id        => Some(id as NodeId),
+        };
+        let label = reader.string()?;
+        let answer = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.string()?),
+        };
+        let mut metadata = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            metadata.insert(reader.string()?, reader.string()?);
+        }
+
+        // Родители всегда записаны раньше детей, поэтому порядок детей сохраняется
+        let node = QANode { kind, label, answer, metadata };
+        if tree.add_node(node, parent).is_none() {
+            return Err(SnapshotError::Corrupted);
+        }
+    }
+
+    // Снапшот другой сборки может ссылаться на ноды, которых нет
+    let question_id = (0..reader.u32()?)
+        .map(|_| match reader.u32()? as NodeId {
+            id if tree.get(id).is_some_and(QANode::is_question) => Ok(id),
+            _ => Err(SnapshotError::Corrupted),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut index = HashMap::new();
+    for _ in 0..reader.u32()? {
+        let label = reader.string()?;
+        let id = reader.u32()? as NodeId;
+        if tree.get(id).is_none_or(|node: &QANode| node.label != label) {
+            return Err(SnapshotError::Corrupted);
+        }
+        index.insert(label, id);
+    }
+    reader.finish()?;
+
+    Ok(QASerde::from_parts(tree, question_id, index))
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) {
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        let end = self.pos.checked_add(len).ok_or(SnapshotError::Truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(SnapshotError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    /// Данные после ожидаемого конца означают, что формат не тот
+    fn finish(&self) -> Result<(), SnapshotError> {
+        match self.pos == self.bytes.len() {
+            true  => Ok(()),
+            false => Err(SnapshotError::Corrupted),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| SnapshotError::Corrupted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Временный каталог теста; удаляется вместе со значением
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "mr-deeds-snapshot-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::SeqCst)));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sample() -> QASerde {
+        QASerde::from_json(json!({ "root": {
+            "Учебный процесс": {
+                "Когда сессия?": "В январе",
+                "Практика": { "Какие сроки сдачи практики?": "1 месяц" },
+            },
+            "Где деканат?": "Корпус 1",
+        }})).unwrap()
+    }
+
+    /// Снапшот с заменёнными данными и пересчитанной контрольной суммой
+    fn write_payload(path: &str, payload: &[u8]) {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(payload);
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn persist_keeps_key_order() {
+        let dir = TempDir::new();
+        let json_path = dir.path("db.json");
+        let source = r#"{
+  "root": {
+    "Я": { "Б?": "2", "А?": "1" },
+    "Где деканат?": "Корпус 1"
+  },
+  "Второй корень": { "В?": "3" }
+}"#;
+        fs::write(&json_path, source).unwrap();
+
+        let qna = QASerde::new().build(&json_path).unwrap();
+        persist(&qna, &json_path, &dir.path("db.snapshot")).unwrap();
+
+        let written = fs::read_to_string(&json_path).unwrap();
+        let keys: Vec<&str> = written.lines().filter_map(|line| line.trim().split('"').nth(1)).collect();
+        assert_eq!(keys, ["root", "Я", "Б?", "А?", "Где деканат?", "Второй корень", "В?"]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new();
+        let qna = sample();
+        save(&qna, 7, &dir.path("db.snapshot")).unwrap();
+
+        let loaded = load(&dir.path("db.snapshot"), Some(7)).unwrap();
+        assert_eq!(loaded.to_json(), qna.to_json());
+        assert_eq!(loaded.question_id, qna.question_id);
+        assert_eq!(loaded.index(), qna.index());
+        assert_eq!(loaded.get_answer("Какие сроки сдачи практики?"), Some("1 месяц"));
+    }
+
+    #[test]
+    fn corrupted_payload() {
+        let dir = TempDir::new();
+        let path = dir.path("db.snapshot");
+        save(&sample(), 7, &path).unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(load(&path, Some(7)), Err(SnapshotError::ChecksumMismatch)));
+
+        bytes[last] ^= 0xff;
+        bytes.push(0);
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(load(&path, Some(7)), Err(SnapshotError::Corrupted)));
+    }
+
+    #[test]
+    fn rejects_unknown_node_ids() {
+        let dir = TempDir::new();
+        let path = dir.path("db.snapshot");
+        let payload = encode(&sample());
+
+        // Последние 4 байта - номер ноды последней записи индекса
+        let mut bad_index = payload.clone();
+        let last = bad_index.len() - 4;
+        bad_index[last..].copy_from_slice(&1000u32.to_le_bytes());
+        write_payload(&path, &bad_index);
+        assert!(matches!(load(&path, None), Err(SnapshotError::Corrupted)));
+
+        let mut trailing = payload;
+        trailing.extend_from_slice(&[0; 4]);
+        write_payload(&path, &trailing);
+        assert!(matches!(load(&path, None), Err(SnapshotError::Corrupted)));
+    }
+
+    #[test]
+    fn rejects_question_ids_of_categories() {
+        let dir = TempDir::new();
+        let path = dir.path("db.snapshot");
+        let mut qna = sample();
+        // Нода 0 - корень, а не вопрос
+        qna.question_id[0] = 0;
+        write_payload(&path, &encode(&qna));
+
+        assert!(matches!(load(&path, None), Err(SnapshotError::Corrupted)));
+    }
+
+    #[test]
+    fn stale_source_crc() {
+        let dir = TempDir::new();
+        save(&sample(), 7, &dir.path("db.snapshot")).unwrap();
+
+        assert!(matches!(load(&dir.path("db.snapshot"), Some(8)), Err(SnapshotError::Stale)));
+    }
+
+    #[test]
+    fn rebuilds_from_json_when_snapshot_is_bad() {
+        let dir = TempDir::new();
+        let (json_path, snapshot_path) = (dir.path("qna.json"), dir.path("db.snapshot"));
+        fs::write(&json_path, serde_json::to_vec(&sample().to_json()).unwrap()).unwrap();
+        fs::write(&snapshot_path, b"QNAS garbage").unwrap();
+
+        let qna = load_or_build(&json_path, &snapshot_path).unwrap();
+        assert_eq!(qna.to_json(), sample().to_json());
+        // Снапшот пересобран и теперь читается
+        let source_crc = crc32fast::hash(&fs::read(&json_path).unwrap());
+        assert!(load(&snapshot_path, Some(source_crc)).is_ok());
+    }
+}
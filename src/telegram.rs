@@ -1,294 +1,323 @@
-use std::error::Error;
+use std::time::Instant;
 
-use frankenstein::{AnswerCallbackQueryParams, Api, BotCommand, EditMessageReplyMarkupParams, GetUpdatesParams, GetUpdatesParamsBuilder, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, MaybeInaccessibleMessage, Message, MessageEntity, MethodResponse, ReplyKeyboardMarkup, ReplyMarkup, SendMessageParams, SendMessageParamsBuilder, SetMyCommandsParams, TelegramApi, UpdateContent};
+use frankenstein::{AnswerCallbackQueryParams, Api, AsyncApi, BotCommand, BotCommandScope, BotCommandScopeChat, CallbackQuery, DeleteWebhookParams, EditMessageReplyMarkupParams, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, MaybeInaccessibleMessage, Message, MessageEntityType, ReplyKeyboardMarkup, ReplyMarkup, SendMessageParams, SetMyCommandsParams, SetWebhookParams, TelegramApi, Update, UpdateContent};
+use tokio::runtime::Handle;
 
-use crate::arenatree::NodeId;
+use crate::admin::{self, Admin};
+use crate::config::{Config, Messages};
+use crate::conversation::{ButtonPress, Conversation, Event, Keyboard, Reply, TextMessage};
 use crate::db;
-use crate::util::logging::{check_result, check_pass, non_fatal, fatal};
-use crate::serde::QASerde;
-use crate::error::telegram::*;
-
-static ROOT: NodeId = 0;
-
+use crate::knowledge::KnowledgeBase;
+use crate::metrics::METRICS;
+use crate::operator::Operators;
+use crate::outbox::{Executor, Outbox, RateLimits, Receipt};
+use crate::polling::{Backoff, PollingConfig};
+use crate::transport::Transport;
+use crate::util::logging::{check_result, info, non_fatal};
+
+/// Telegram-фронтенд бота: получает обновления Bot API и доставляет
+/// ответы ядра через очередь исходящих вызовов
 pub struct TelegramSender {
     api: Api,
-    database_rw: QASerde,
-    send_params: SendMessageParamsBuilder,
-    update_params: GetUpdatesParamsBuilder,
-    reply_markup: ReplyMarkup,
-    question_db: db::Database,
+    conversation: Conversation,
+    polling: PollingConfig,
+    outbox: Outbox,
 }
 
-impl TelegramSender {
-    /// Конструктор, создающий нового бота
-    pub fn new(token: &str, dbpath: &str, question_db: db::Database) -> Self {
-        let api = Api::new(token);
-
-        Self::build_commands(&api);
-
-        let db = QASerde::new().build(dbpath).unwrap();
-
-        match Self::build_inline_keyboard() {
-            Ok(keyboard_markup) =>  {
-                TelegramSender {
-                    api,
-                    database_rw: db,
-                    send_params: SendMessageParams::builder(),
-                    update_params: GetUpdatesParams::builder(),
-                    reply_markup: ReplyMarkup::InlineKeyboardMarkup(keyboard_markup),
-                    question_db,
-                }
-            }
-            Err(error) => {
-                fatal(error);
-                unreachable!()
-            }
-        }
-            
+/// Вызов Bot API, который нужно выполнить в ответ на обновление
+#[derive(Debug, Clone)]
+pub enum Action {
+    SendMessage(SendMessageParams),
+    AnswerCallbackQuery(AnswerCallbackQueryParams),
+    EditMessageReplyMarkup(EditMessageReplyMarkupParams),
+    /// Файл в чат; очередь сама запишет его на диск для загрузки
+    SendDocument { chat_id: i64, file_name: String, content: Vec<u8> },
+}
+
+impl Action {
+    /// Сообщения в чат, на которые действуют ограничения частоты Telegram
+    pub fn is_message(&self) -> bool {
+        matches!(self, Action::SendMessage(_) | Action::SendDocument { .. })
     }
 
-    fn build_choice_keyboard(&mut self, parent: Option<String>) -> Result<(), Box<dyn Error>> {
-        eprintln!("Строим клавиатуру с родителем {parent:?}");
-        let mut choice_keyboard: Vec<Vec<KeyboardButton>> = vec![];
+    /// Метод Bot API, для метрик
+    pub fn method(&self) -> &'static str {
+        match self {
+            Action::SendMessage(_) => "sendMessage",
+            Action::AnswerCallbackQuery(_) => "answerCallbackQuery",
+            Action::EditMessageReplyMarkup(_) => "editMessageReplyMarkup",
+            Action::SendDocument { .. } => "sendDocument",
+        }
+    }
+}
 
-        let children = self.database_rw.get_children(parent)?;
-        for question in children {
-            choice_keyboard.push(vec![KeyboardButton::builder().text(question).build()]);
+impl TelegramSender {
+    /// Конструктор, создающий нового бота. К Telegram не обращается.
+    /// С `runtime` ответы отправляются асинхронным клиентом в его задачах
+    pub fn new(config: &Config, knowledge: KnowledgeBase, question_db: db::Database, runtime: Option<Handle>) -> Self {
+        let api = Api::new_url(config.bot_api_url());
+        let executor = match runtime {
+            Some(runtime) => Executor::Async { api: AsyncApi::new_url(config.bot_api_url()), runtime },
+            None => Executor::Blocking(api.clone()),
+        };
+
+        TelegramSender {
+            outbox: Outbox::start(executor, RateLimits::default()),
+            api,
+            conversation: Conversation::new(knowledge, config.messages.clone(), question_db).with_admin(Admin::new(config))
+                                                                                           .with_operators(Operators::new(config)),
+            polling: config.polling_config(),
         }
-        eprintln!("Элементы клавиатуры: {choice_keyboard:?}");
+    }
 
-        self.reply_markup = ReplyMarkup::ReplyKeyboardMarkup(ReplyKeyboardMarkup::builder().keyboard(choice_keyboard).build());
-        Ok(())
+    /// Регистрирует команды бота в меню Telegram. Администраторы
+    /// видят в своих чатах ещё и свои команды
+    pub fn register_commands(&self) {
+        let mut commands = Self::build_commands(self.conversation.messages());
+        if self.conversation.operators().is_enabled() {
+            commands.push(BotCommand::builder().command("operator").description(&self.conversation.messages().command_operator).build());
+        }
+        let params = SetMyCommandsParams::builder().commands(commands.clone()).build();
+        check_result(self.api.set_my_commands(&params), non_fatal);
+
+        let admin_commands: Vec<BotCommand> = commands.into_iter()
+                                                      .chain(admin::commands(self.conversation.messages()).map(|(command, description)| {
+                                                          BotCommand::builder().command(command).description(description).build()
+                                                      }))
+                                                      .collect();
+        for &admin in self.conversation.admin().admins() {
+            let scope = BotCommandScope::Chat(BotCommandScopeChat::builder().chat_id(admin as i64).build());
+            let params = SetMyCommandsParams::builder().commands(admin_commands.clone()).scope(scope).build();
+            check_result(self.api.set_my_commands(&params), non_fatal);
+        }
     }
 
-    fn build_inline_keyboard() -> Result<InlineKeyboardMarkup, <frankenstein::Api as TelegramApi>::Error> {
-        let mut inline_keyboard: Vec<Vec<InlineKeyboardButton>> = vec![];
-        inline_keyboard.push(vec![
-            InlineKeyboardButton::builder().text("Да").callback_data("save").build(),
-            InlineKeyboardButton::builder().text("Нет").callback_data("nosave").build(),
-        ]);
+    pub fn polling(&self) -> &PollingConfig {
+        &self.polling
+    }
 
-        Ok(InlineKeyboardMarkup::builder().inline_keyboard(inline_keyboard).build())
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
     }
 
-    fn build_commands(api: &Api) -> Result<(), <frankenstein::Api as TelegramApi>::Error> {
-        let mut commandvec: Vec<BotCommand> = vec![];
-        commandvec.push(BotCommand::builder()
+    fn build_commands(messages: &Messages) -> Vec<BotCommand> {
+        vec![
+            BotCommand::builder()
                         .command("start")
-                        .description("Начать работу")
-        .build());
-        commandvec.push(BotCommand::builder()
+                        .description(&messages.command_start)
+            .build(),
+            BotCommand::builder()
                         .command("reset")
-                        .description("Вернуться в начало")
-        .build());
-        commandvec.push(BotCommand::builder()
+                        .description(&messages.command_reset)
+            .build(),
+            BotCommand::builder()
                         .command("help")
-                        .description("Помощь")
-        .build());
-
-        let commandparams = SetMyCommandsParams::builder().commands(commandvec).build();
+                        .description(&messages.command_help)
+            .build(),
+        ]
+    }
 
-        let _ = api.set_my_commands(&commandparams)?;
+    /// Включает доставку обновлений на вебхук; Telegram будет присылать `secret`
+    /// в заголовке каждого запроса
+    pub fn set_webhook(&self, url: &str, secret: &str) -> Result<(), frankenstein::Error> {
+        let params = SetWebhookParams::builder().url(url).secret_token(secret).build();
 
+        self.api.set_webhook(&params)?;
         Ok(())
     }
 
-    pub fn start_reply_loop(&mut self) {
-        let mut built_update_params: GetUpdatesParams;
-        built_update_params = self.update_params.clone().build();
+    pub fn start_reply_loop(&self) {
+        // getUpdates не работает, пока установлен вебхук
+        check_result(self.api.delete_webhook(&DeleteWebhookParams::builder().build()), non_fatal);
 
-        let mut prev_message: Option<Message> = None;
+        let mut offset: Option<i64> = None;
+        let mut backoff = Backoff::new(&self.polling);
 
         loop {
+            let started = Instant::now();
             let result = self.api
-                             .get_updates(&built_update_params);
+                             .get_updates(&self.polling.params(offset));
+            METRICS.poll(started.elapsed(), result.is_ok());
 
             match result {
                 Ok(response) => {
+                    backoff.reset();
                     for update in response.result {
-                        match update.content {
-                            // Сообщение
-                            UpdateContent::Message(message) 
-                                => {
-                                    prev_message = Some(message.clone());
-                                    check_result(self.process_message(message), non_fatal);
-                                },
-
-                            // Ввод пользователя посредством кнопок
-                            UpdateContent::CallbackQuery(callback) 
-                                => {
-                                // Сохраняем вопрос, если пользователь хочет        
-                                if let Some(save_option) = callback.data {
-                                    if save_option == "save" {
-                                        if let Some(message) = prev_message.take() {
-                                            self.save_question(message);
-                                        }
-                                    }
-                                }
-                                // Ответ пользователю, что мы всё обработали
-                                let callback_params = AnswerCallbackQueryParams::builder()
-                                                      .text("Я сохранил твой выбор, спасибо!")
-                                                      .callback_query_id(callback.id)
-                                                      .build();
-                                check_result(self.api.answer_callback_query(&callback_params), non_fatal);
-
-                                // Убираем кнопки с предыдущего сообщения
-                                if let Some(msg) = callback.message {
-                                    use MaybeInaccessibleMessage::{Message, InaccessibleMessage};
-                                    let (chat_id, msg_id) = match msg 
-                                    {
-                                        Message(message) => (message.chat.id, message.message_id),
-                                        InaccessibleMessage(message) => (message.chat.id, message.message_id),
-                                    };
-                                    let edit_message_params = EditMessageReplyMarkupParams::builder()
-                                                              .chat_id(chat_id)
-                                                              .message_id(msg_id)
-                                                              .reply_markup(InlineKeyboardMarkup::builder()
-                                                                            .inline_keyboard(vec![vec![]])
-                                                                            .build())
-                                                              .build();
-
-                                    check_result(self.api.edit_message_reply_markup(&edit_message_params), non_fatal);
-                                }
-                            }
-
-                            _ => (),
-                        }
-
-                        built_update_params = self.update_params.clone().offset(update.update_id + 1).build();
+                        offset = Some(i64::from(update.update_id) + 1);
+                        self.dispatch(update);
                     }
                 }
                 Err(error) => {
+                    // Не долбим API во время сбоя
+                    let delay = backoff.delay(&error);
                     non_fatal(error);
+                    info(&format!("Повторный запрос обновлений через {delay:?}"));
+                    std::thread::sleep(delay);
                 }
             }
         }
     }
 
-    fn process_message(&mut self, message: Message) -> Result<(), frankenstein::Error> {
-        // Наверное, вопрос, ищем ответ
-        if let Some(question) = message.text {
-            // Проверяем, если пользователь отправил команду, и отправляем 
-            // соответствующий ответ, если да
-            if let Some(entities) = message.entities {
-                if let Some(cmd_answer) = self.get_command_reply(entities, &question) {
-                    self.send_message(message.chat.id, cmd_answer)?;
-                    return Ok(());
-                }
-            }
-            // Не команда. Значит, вопрос?
-            if let Some(answer) = self.query_question(question.clone()) {
-                self.send_message(message.chat.id, answer)?;
-            } else if self.database_rw.contains(&question) {
-                // Ответ не найден, но есть такая категория
-                self.send_message(message.chat.id, format!("Категория: \"{}\"",question))?;
-            } else {
-                // Пользователь ввёл что-то невнятное. На всякий случай запишем
-                // Создадим клавиатуру для выбора юзера
-                match Self::build_inline_keyboard() {
-                    Ok(keyboard) => self.reply_markup = ReplyMarkup::InlineKeyboardMarkup(keyboard),
-                    Err(error) => non_fatal(error),
-                }
-                self.send_message(
-                    message.chat.id, 
-                    "Прости, я не знаю ответа на твой вопрос...".to_string())?;
-            }
-        } else {
-            // Пользователь зачем-то отправил что-то 
-            // другое вместо текстового сообщения
-            self.send_message(
-                message.chat.id, 
-                "Извини, я понимаю только вопросы текстом!".to_string()
-            )?;
+    /// Чат, к которому относится обновление. Обновления одного чата
+    /// нужно обрабатывать по порядку
+    pub fn update_chat_id(update: &Update) -> Option<i64> {
+        match &update.content {
+            UpdateContent::Message(message) => Some(message.chat.id),
+            UpdateContent::CallbackQuery(callback) => Some(match &callback.message {
+                Some(MaybeInaccessibleMessage::Message(message)) => message.chat.id,
+                Some(MaybeInaccessibleMessage::InaccessibleMessage(message)) => message.chat.id,
+                None => callback.from.id as i64,
+            }),
+            _ => None,
         }
-
-        return Ok(());
     }
 
-    fn get_command_reply(&mut self, entities: Vec<MessageEntity>, message: &str) -> Option<String> {
-        for e in entities {
-            match e.type_field {
-                frankenstein::MessageEntityType::BotCommand => {
-                    return Some(self.match_command(message))
-                }
-                _ => continue,
-            }
-        }
+    fn message_event(message: Message) -> Event {
+        let Some(text) = message.text else {
+            return Event::Unsupported { chat_id: message.chat.id };
+        };
+        let is_command = message.entities
+                                .unwrap_or_default()
+                                .iter()
+                                .any(|entity| entity.type_field == MessageEntityType::BotCommand);
+
+        Event::Text(TextMessage {
+            chat_id: message.chat.id,
+            user_id: message.from.as_ref().map_or(0, |user| user.id),
+            username: message.from.and_then(|user| user.username),
+            text,
+            is_command,
+            reply_to: message.reply_to_message.map(|replied| replied.message_id),
+        })
+    }
 
-        None
+    fn callback_event(callback: CallbackQuery) -> Event {
+        use MaybeInaccessibleMessage::{Message, InaccessibleMessage};
+        let chat_message_id = callback.message.map(|msg| match msg {
+            Message(message) => (message.chat.id, message.message_id),
+            InaccessibleMessage(message) => (message.chat.id, message.message_id),
+        });
+
+        Event::Button(ButtonPress {
+            id: callback.id,
+            chat_id: chat_message_id.map_or(callback.from.id as i64, |(chat_id, _)| chat_id),
+            user_id: callback.from.id,
+            message_id: chat_message_id.map(|(_, message_id)| message_id),
+            data: callback.data.unwrap_or_default(),
+        })
     }
 
-    fn reset_choice_keyboard(&mut self) -> Result<(),Box<dyn Error>>  {
-        eprintln!("Клавиатура сброшена");
-        self.build_choice_keyboard(None)?;
+    /// Вызов Bot API для ответа ядра и чат, в очередь которого его поставить
+    fn action(reply: Reply) -> (Option<i64>, Action) {
+        match reply {
+            Reply::Answer { chat_id, text, .. } => Self::action(Reply::Message { chat_id, text, keyboard: None }),
+            Reply::Forward { chat_id, text, keyboard, .. } | Reply::Relay { chat_id, text, keyboard, .. }
+                => Self::action(Reply::Message { chat_id, text, keyboard }),
+            Reply::Message { chat_id, text, keyboard } => {
+                let send_params_builder = SendMessageParams::builder()
+                                  .chat_id(chat_id)
+                                  .text(text);
+
+                (Some(chat_id), Action::SendMessage(match keyboard.map(Self::markup) {
+                    Some(markup) => send_params_builder.reply_markup(markup).build(),
+                    None         => send_params_builder.build(),
+                }))
+            }
+            Reply::Notify { press_id, text } => {
+                let callback_params = AnswerCallbackQueryParams::builder()
+                                      .text(text)
+                                      .callback_query_id(press_id)
+                                      .build();
 
-        Ok(())
+                (None, Action::AnswerCallbackQuery(callback_params))
+            }
+            Reply::RemoveButtons { chat_id, message_id } => {
+                let edit_message_params = EditMessageReplyMarkupParams::builder()
+                                          .chat_id(chat_id)
+                                          .message_id(message_id)
+                                          .reply_markup(InlineKeyboardMarkup::builder()
+                                                        .inline_keyboard(vec![vec![]])
+                                                        .build())
+                                          .build();
+
+                (Some(chat_id), Action::EditMessageReplyMarkup(edit_message_params))
+            }
+            Reply::Document { chat_id, file_name, content } => (Some(chat_id), Action::SendDocument { chat_id, file_name, content }),
+        }
     }
 
-    fn match_command(&mut self, msg: &str) -> String {
-        match msg {
-            "/start" => {
-                self.reset_choice_keyboard();
-                "Привет! Я бот!"
-            },
-            "/help"  => "Выбери вопрос или напиши свой",
-            "/reset" => {
-                self.reset_choice_keyboard();
-                "Вернулись в начало."
-            },
-            _        => "Неизвестная команда",
+    fn markup(keyboard: Keyboard) -> ReplyMarkup {
+        match keyboard {
+            Keyboard::Choices(choices) => {
+                let keyboard: Vec<Vec<KeyboardButton>> = choices.into_iter()
+                                                                .map(|choice| vec![KeyboardButton::builder().text(choice).build()])
+                                                                .collect();
+                ReplyMarkup::ReplyKeyboardMarkup(ReplyKeyboardMarkup::builder().keyboard(keyboard).build())
+            }
+            Keyboard::Inline(buttons) => {
+                let row: Vec<InlineKeyboardButton> = buttons.into_iter()
+                                                            .map(|button| InlineKeyboardButton::builder()
+                                                                              .text(button.text)
+                                                                              .callback_data(button.data)
+                                                                              .build())
+                                                            .collect();
+                ReplyMarkup::InlineKeyboardMarkup(InlineKeyboardMarkup::builder().inline_keyboard(vec![row]).build())
+            }
         }
-        .to_string()
     }
+}
+
+impl Transport for TelegramSender {
+    type Update = Update;
 
-    fn send_message(&self, id: i64, message: String) 
-    -> Result<MethodResponse<Message>, frankenstein::Error> 
-    {
-        let send_params_builder = SendMessageParams::builder()
-                          .chat_id(id)
-                          .text(message);
-
-        let send_params: SendMessageParams;
-        // if let Some(markup) = reply_markup {
-        send_params = send_params_builder.reply_markup(self.reply_markup.clone()).build();
-        // } else {
-            // send_params = send_params_builder.build();
-        // }
-            
-        eprintln!("Отправляем с reply_markup {:?}", self.reply_markup);
-        self.api.send_message(&send_params)
+    fn conversation(&self) -> &Conversation {
+        &self.conversation
     }
 
-    fn query_question(&mut self, question: String) -> Option<String> {
-        let children = check_pass(self.database_rw.get_children(Some(question.clone())), non_fatal);
-        // Найдены дети текущего вопроса
-        if let Some(arr) = children {
-            // Ребёнок один и он является ответом
-            if arr.len() == 1 && self.database_rw.is_question(&question) {
-                self.reset_choice_keyboard();
-                return arr.get(0).cloned();
-            } 
-            // Дети являются категориями, идём глубже
-            else {
-                self.build_choice_keyboard(Some(question));
-            }
-        } 
-        else {
-            // Детей нет - не знаем такого вопроса/категории
-            self.reset_choice_keyboard();
+    fn event(&self, update: Update) -> Option<Event> {
+        METRICS.update_received();
+        match update.content {
+            // Сообщение
+            UpdateContent::Message(message) => Some(Self::message_event(message)),
+            // Ввод пользователя посредством кнопок
+            UpdateContent::CallbackQuery(callback) => Some(Self::callback_event(callback)),
+            _ => None,
         }
-
-        None
     }
 
-    fn save_question(&mut self, message: Message) {
-            // Сохраняем вопрос 
-            eprintln!("Cохраняем вопрос: {message:?}");
-            if let Some(message_text) = &message.text {
-                let user_id = message.from.as_ref().map_or(0, |x| x.id);
-                check_result(self.question_db.insert_question(&message_text, user_id), non_fatal);
+    /// Ставит ответы в очередь исходящих вызовов
+    fn deliver(&self, replies: Vec<Reply>) {
+        for reply in replies {
+            let db = self.conversation.question_db().clone();
+            let receipt: Option<Receipt> = match reply {
+                Reply::Answer { question_id, .. } => Some(Box::new(move |result: Result<Option<i32>, String>| {
+                    check_result(db.record_delivery(question_id, result.map(|_| ())), non_fatal)
+                })),
+                // Запоминаем сообщение, чтобы ответ на него нашёл вопрос
+                Reply::Forward { question_id, chat_id, .. } => Some(Box::new(move |result: Result<Option<i32>, String>| {
+                    if let Ok(Some(message_id)) = result {
+                        check_result(db.remember_forward(chat_id, message_id, question_id), non_fatal);
+                    }
+                })),
+                Reply::Relay { ticket_id, chat_id, .. } => Some(Box::new(move |result: Result<Option<i32>, String>| {
+                    if let Ok(Some(message_id)) = result {
+                        check_result(db.remember_relay(chat_id, message_id, ticket_id), non_fatal);
+                    }
+                })),
+                _ => None,
+            };
+            let (chat_id, action) = Self::action(reply);
+
+            match receipt {
+                Some(receipt) => self.outbox.enqueue_with_receipt(chat_id, action, receipt),
+                None => self.outbox.enqueue(chat_id, vec![action]),
             }
+        }
     }
 }
 
-
+// Обработчики обновлений делят бота между потоками
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<TelegramSender>();
+};
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use frankenstein::{AsyncApi, AsyncTelegramApi, DeleteWebhookParams, Update};
+use tokio::sync::mpsc;
+
+use crate::metrics::METRICS;
+use crate::polling::Backoff;
+use crate::telegram::TelegramSender;
+use crate::transport::Transport;
+use crate::util::logging::{check_result, info, non_fatal};
+
+/// Асинхронный режим: обновления получаются асинхронным клиентом и
+/// раздаются обработчикам по чатам. Внутри одного чата обновления
+/// обрабатываются строго по порядку, разные чаты - параллельно
+pub struct AsyncRunner {
+    bot: Arc<TelegramSender>,
+    api: AsyncApi,
+    workers: HashMap<i64, ChatWorker>,
+}
+
+/// Обработчик одного чата
+struct ChatWorker {
+    sender: mpsc::UnboundedSender<Update>,
+    /// Сколько обновлений отправлено обработчику, но ещё не обработано
+    pending: Arc<AtomicUsize>,
+}
+
+// Обновления без чата обрабатываются общим обработчиком
+const NO_CHAT: i64 = 0;
+
+impl AsyncRunner {
+    /// `api_url` - адрес Bot API вместе с токеном, см. `Config::bot_api_url`
+    pub fn new(api_url: &str, bot: Arc<TelegramSender>) -> Self {
+        AsyncRunner { bot, api: AsyncApi::new_url(api_url), workers: HashMap::new() }
+    }
+
+    pub async fn start_reply_loop(mut self) {
+        // getUpdates не работает, пока установлен вебхук
+        check_result(self.api.delete_webhook(&DeleteWebhookParams::builder().build()).await, non_fatal);
+
+        let polling = self.bot.polling().clone();
+        let mut offset: Option<i64> = None;
+        let mut backoff = Backoff::new(&polling);
+
+        loop {
+            let started = Instant::now();
+            let result = self.api.get_updates(&polling.params(offset)).await;
+            METRICS.poll(started.elapsed(), result.is_ok());
+            match result {
+                Ok(response) => {
+                    backoff.reset();
+                    for update in response.result {
+                        offset = Some(i64::from(update.update_id) + 1);
+                        self.dispatch(update);
+                    }
+                    self.prune_idle_workers();
+                }
+                Err(error) => {
+                    let delay = backoff.delay(&error);
+                    non_fatal(error);
+                    info(&format!("Повторный запрос обновлений через {delay:?}"));
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, update: Update) {
+        let chat_id = TelegramSender::update_chat_id(&update).unwrap_or(NO_CHAT);
+        let worker = self.workers
+                         .entry(chat_id)
+                         .or_insert_with(|| ChatWorker::spawn(Arc::clone(&self.bot)));
+
+        worker.pending.fetch_add(1, Ordering::SeqCst);
+        if let Err(error) = worker.sender.send(update) {
+            worker.pending.fetch_sub(1, Ordering::SeqCst);
+            non_fatal(error);
+        }
+    }
+
+    /// Останавливает обработчики, у которых не осталось обновлений. Новые
+    /// обновления чата получит новый обработчик, так что порядок не нарушится
+    fn prune_idle_workers(&mut self) {
+        self.workers.retain(|_, worker| worker.pending.load(Ordering::SeqCst) > 0);
+    }
+}
+
+impl ChatWorker {
+    fn spawn(bot: Arc<TelegramSender>) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Update>();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let worker_pending = Arc::clone(&pending);
+
+        tokio::spawn(async move {
+            while let Some(update) = receiver.recv().await {
+                // Обработка может обращаться к SQLite, поэтому выносим её из асинхронного потока.
+                // Ответы уходят через общую очередь исходящих с учётом ограничений Telegram,
+                // а выполняются задачами рантайма, так что медленный чат не держит остальные
+                let handler = Arc::clone(&bot);
+                check_result(tokio::task::spawn_blocking(move || handler.dispatch(update)).await, non_fatal);
+                worker_pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+
+        ChatWorker { sender, pending }
+    }
+}
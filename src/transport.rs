@@ -0,0 +1,23 @@
+use crate::conversation::{Conversation, Event, Reply};
+
+/// Мессенджер, через который бот общается с пользователями. Переводит
+/// обновления мессенджера в события ядра и доставляет его ответы
+pub trait Transport {
+    /// Обновление в формате мессенджера
+    type Update;
+
+    fn conversation(&self) -> &Conversation;
+
+    /// Событие для ядра; `None`, если обновление боту неинтересно
+    fn event(&self, update: Self::Update) -> Option<Event>;
+
+    /// Доставляет ответы ядра по порядку
+    fn deliver(&self, replies: Vec<Reply>);
+
+    /// Обрабатывает обновление целиком
+    fn dispatch(&self, update: Self::Update) {
+        if let Some(event) = self.event(update) {
+            self.deliver(self.conversation().handle(event));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+
+use frankenstein::Update;
+use tiny_http::{Request, Response, Server};
+
+use crate::telegram::TelegramSender;
+use crate::transport::Transport;
+use crate::util::logging::{check_result, info, non_fatal};
+
+const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Режим вебхука: Telegram сам присылает обновления POST-запросами на
+/// встроенный HTTP-сервер. Локально можно проверить, отправив записанное
+/// обновление: `curl -H "X-Telegram-Bot-Api-Secret-Token: <секрет>" -d @update.json <адрес>`
+pub struct WebhookServer {
+    server: Server,
+    secret: String,
+}
+
+impl WebhookServer {
+    pub fn bind(listen: &str, secret: &str) -> Result<Self, Box<dyn Error>> {
+        let server = Server::http(listen).map_err(|error| error as Box<dyn Error>)?;
+
+        Ok(WebhookServer { server, secret: secret.to_owned() })
+    }
+
+    /// Принимает обновления и обрабатывает их тем же кодом, что и long polling.
+    /// Telegram получает ответ сразу, обработка идёт после, по порядку
+    pub fn start_reply_loop(&self, bot: &TelegramSender) {
+        for request in self.server.incoming_requests() {
+            if let Some(update) = self.accept(request) {
+                bot.dispatch(update);
+            }
+        }
+    }
+
+    fn accept(&self, mut request: Request) -> Option<Update> {
+        if request.method() != &tiny_http::Method::Post {
+            check_result(request.respond(Response::empty(405)), non_fatal);
+            return None;
+        }
+
+        // Пустой секрет отсекает проверка конфигурации, но и с ним запрос не пройдёт
+        let token = request.headers()
+                           .iter()
+                           .find(|header| header.field.equiv(SECRET_HEADER))
+                           .map(|header| header.value.as_str());
+
+        if self.secret.is_empty() || !token.is_some_and(|token| constant_time_eq(token.as_bytes(), self.secret.as_bytes())) {
+            info("Запрос к вебхуку с неверным секретом");
+            check_result(request.respond(Response::empty(401)), non_fatal);
+            return None;
+        }
+
+        let mut body = String::new();
+        if let Err(error) = request.as_reader().read_to_string(&mut body) {
+            non_fatal(error);
+            check_result(request.respond(Response::empty(400)), non_fatal);
+            return None;
+        }
+
+        match serde_json::from_str::<Update>(&body) {
+            Ok(update) => {
+                check_result(request.respond(Response::empty(200)), non_fatal);
+                Some(update)
+            }
+            Err(error) => {
+                non_fatal(error);
+                check_result(request.respond(Response::empty(400)), non_fatal);
+                None
+            }
+        }
+    }
+}
+
+/// Сравнение секрета за время, не зависящее от места первого расхождения
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,607 @@
+//! Команды администраторов через локальную замену Bot API
+
+mod common;
+
+use common::{inline_buttons, keyboard, messages, Bot, MockApi};
+use serde_json::json;
+
+const ADMIN: i64 = 7;
+const USER: i64 = 42;
+/// Группа администраторов и её участник, которого нет в списке администраторов
+const GROUP: i64 = -100;
+const MEMBER: i64 = 100;
+
+fn start(api: &MockApi) -> Bot {
+    Bot::start_with(api, "polling", &[("BOT_ADMINS", &ADMIN.to_string())])
+}
+
+#[test]
+fn registers_admin_commands_for_admin_chats() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    let everyone = api.expect("setMyCommands");
+    assert!(everyone.params.get("scope").is_none());
+
+    let admins = api.expect("setMyCommands");
+    assert_eq!(admins.params["scope"], json!({ "type": "chat", "chat_id": ADMIN }));
+    let commands: Vec<&str> = admins.params["commands"]
+                                    .as_array()
+                                    .unwrap()
+                                    .iter()
+                                    .filter_map(|command| command["command"].as_str())
+                                    .collect();
+    assert_eq!(commands, ["start", "reset", "help", "reload", "stats", "pending", "answer", "broadcast", "promote", "feedback"]);
+}
+
+#[test]
+fn refuses_admin_commands_to_users() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    api.send_text(USER, "/stats");
+    let reply = api.expect_message(USER);
+    assert_eq!(reply.params["text"], "Эта команда доступна только администраторам");
+}
+
+#[test]
+fn reload_reports_changes() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    api.send_text(ADMIN, "/reload");
+    let reply = api.expect_message(ADMIN);
+    assert_eq!(reply.params["text"], "База знаний перезагружена, вопросов: 4\nИзменений нет.");
+}
+
+/// Пользователь задаёт неизвестный вопрос и соглашается его сохранить
+fn save_question(api: &MockApi, question: &str) {
+    save_question_from(api, USER, question);
+}
+
+fn save_question_from(api: &MockApi, user: i64, question: &str) {
+    api.send_text(user, question);
+    let reply = api.expect_message(user);
+    api.press_button(user, reply.result["message_id"].as_i64().unwrap(), "save");
+    api.expect("answerCallbackQuery");
+    api.expect("editMessageReplyMarkup");
+}
+
+#[test]
+fn pending_shows_open_questions_with_moderation_buttons() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/pending");
+    let header = api.expect_message(ADMIN);
+    assert_eq!(header.params["text"], "Вопросов на модерации: 1, страница 1 из 1");
+    let item = api.expect_message(ADMIN);
+    assert!(item.params["text"].as_str().unwrap().ends_with("\nГде столовая?"), "{item:?}");
+    assert_eq!(inline_buttons(&item), ["mod:approve:1", "mod:reject:1", "mod:answer:1", "mod:duplicate:1", "mod:promote:1"]);
+
+    api.press_button(ADMIN, item.result["message_id"].as_i64().unwrap(), "mod:reject:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Вопрос #1 отклонён");
+    api.expect("editMessageReplyMarkup");
+
+    api.send_text(ADMIN, "/pending");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Вопросов на модерации нет.");
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let status: String = db.query_row("select status from questions where id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(status, "rejected");
+}
+
+#[test]
+fn admin_texts_follow_bot_language() {
+    let api = MockApi::start();
+    let _bot = Bot::start_with(&api, "polling", &[("BOT_ADMINS", &ADMIN.to_string()), ("BOT_LANGUAGE", "en")]);
+
+    api.expect("setMyCommands");
+    let admins = api.expect("setMyCommands");
+    let reload = admins.params["commands"].as_array().unwrap().iter().find(|command| command["command"] == "reload").unwrap();
+    assert_eq!(reload["description"], "Reload the knowledge base");
+
+    save_question(&api, "Where is the canteen?");
+    api.send_text(ADMIN, "/pending");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Questions awaiting moderation: 1, page 1 of 1");
+    let item = api.expect_message(ADMIN);
+    let labels: Vec<&str> = item.params["reply_markup"]["inline_keyboard"][0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|button| button["text"].as_str())
+        .collect();
+    assert!(labels.contains(&"Reject"), "{labels:?}");
+
+    api.press_button(ADMIN, item.result["message_id"].as_i64().unwrap(), "mod:reject:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Question #1 rejected");
+}
+
+#[test]
+fn answer_button_takes_next_message_as_answer() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/pending");
+    api.expect_message(ADMIN);
+    let item = api.expect_message(ADMIN);
+    api.press_button(ADMIN, item.result["message_id"].as_i64().unwrap(), "mod:answer:1");
+    api.expect("answerCallbackQuery");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Напишите ответ на вопрос #1 одним сообщением:\nГде столовая?");
+
+    api.send_text(ADMIN, "На первом этаже");
+    assert_eq!(messages(&api, 2), [
+        (ADMIN, "Ответ на вопрос #1 сохранён и отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где столовая?\":\nНа первом этаже".to_string()),
+    ]);
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let answered: (String, String) = db.query_row("select status, answer from questions where id = 1", [], |row| Ok((row.get(0)?, row.get(1)?)))
+                                       .unwrap();
+    assert_eq!(answered, ("answered".to_string(), "На первом этаже".to_string()));
+}
+
+/// Администратор нажимает "Ответить" под вопросом #1; id сообщения с просьбой написать ответ
+fn press_answer(api: &MockApi) -> i64 {
+    api.send_text(ADMIN, "/pending");
+    api.expect_message(ADMIN);
+    let item = api.expect_message(ADMIN);
+    api.press_button(ADMIN, item.result["message_id"].as_i64().unwrap(), "mod:answer:1");
+    api.expect("answerCallbackQuery");
+    let prompt = api.expect_message(ADMIN);
+    assert_eq!(inline_buttons(&prompt), ["mod:cancel:1"]);
+    prompt.result["message_id"].as_i64().unwrap()
+}
+
+fn answer_of(bot: &Bot, id: i64) -> Option<String> {
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    db.query_row("select answer from questions where id = ?1", [id], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn answer_can_be_cancelled() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+    let prompt = press_answer(&api);
+
+    api.press_button(ADMIN, prompt, "mod:cancel:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Ответ на вопрос #1 отменён");
+    api.expect("editMessageReplyMarkup");
+
+    // Теперь это обычный вопрос боту, а не ответ
+    api.send_text(ADMIN, "На первом этаже");
+    assert_ne!(api.expect_message(ADMIN).params["text"], "Ответ на вопрос #1 сохранён и отправляется автору");
+    assert_eq!(answer_of(&bot, 1), None);
+}
+
+#[test]
+fn answer_expires() {
+    let api = MockApi::start();
+    let bot = Bot::start_with(&api, "polling", &[("BOT_ADMINS", &ADMIN.to_string()), ("BOT_ADMIN_TIMEOUT", "1")]);
+    save_question(&api, "Где столовая?");
+    press_answer(&api);
+
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    api.send_text(ADMIN, "На первом этаже");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Время ожидания истекло, действие отменено. Сообщение не сохранено.");
+    assert_eq!(answer_of(&bot, 1), None);
+}
+
+#[test]
+fn answer_reaches_asker() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/answer 1 На первом этаже");
+    assert_eq!(messages(&api, 2), [
+        (ADMIN, "Ответ на вопрос #1 сохранён и отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где столовая?\":\nНа первом этаже".to_string()),
+    ]);
+}
+
+#[test]
+fn promote_adds_question_to_knowledge_base() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/pending");
+    api.expect_message(ADMIN);
+    let item = api.expect_message(ADMIN);
+    api.press_button(ADMIN, item.result["message_id"].as_i64().unwrap(), "mod:promote:1");
+    api.expect("answerCallbackQuery");
+    let browse = api.expect_message(ADMIN);
+    assert_eq!(browse.params["text"], "Выберите категорию для вопроса #1:\nГде столовая?");
+    assert_eq!(keyboard(&browse), ["Учебный процесс", "❌ Отмена"]);
+
+    api.send_text(ADMIN, "Учебный процесс");
+    let browse = api.expect_message(ADMIN);
+    assert_eq!(keyboard(&browse), ["ЦиТХИн", "МСЭН", "✅ Сюда", "⬆️ Назад", "❌ Отмена"]);
+    api.send_text(ADMIN, "МСЭН");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Категория \"Учебный процесс / МСЭН\"");
+    api.send_text(ADMIN, "✅ Сюда");
+    let prompt = api.expect_message(ADMIN);
+    assert_eq!(prompt.params["text"], "Вопрос #1 попадёт в категорию \"Учебный процесс / МСЭН\". Напишите ответ одним сообщением:");
+    assert_eq!(keyboard(&prompt), ["❌ Отмена"]);
+
+    api.send_text(ADMIN, "На первом этаже");
+    assert_eq!(messages(&api, 3), [
+        (ADMIN, "Вопрос #1 добавлен в базу знаний: Учебный процесс / МСЭН / Где столовая?".to_string()),
+        (ADMIN, "Ответ на вопрос #1 сохранён и отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где столовая?\":\nНа первом этаже".to_string()),
+    ]);
+
+    // Вопрос сразу доступен всем и сохранён в файле базы знаний
+    api.send_text(USER, "Учебный процесс");
+    api.expect_message(USER);
+    api.send_text(USER, "МСЭН");
+    assert_eq!(keyboard(&api.expect_message(USER)), ["АБГВ", "Где столовая?"]);
+    api.send_text(USER, "Где столовая?");
+    assert_eq!(api.expect_message(USER).params["text"], "На первом этаже");
+
+    let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(bot.knowledge_base()).unwrap()).unwrap();
+    assert_eq!(saved["root"]["Учебный процесс"]["МСЭН"]["Где столовая?"], "На первом этаже");
+    assert_eq!(saved["root"]["Учебный процесс"]["ЦиТХИн"]["ИКТ"]["Какие сроки сдачи практики?"], "1 месяц");
+}
+
+#[test]
+fn promote_can_be_cancelled() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/promote 1");
+    api.expect_message(ADMIN);
+    api.send_text(ADMIN, "❌ Отмена");
+    let reply = api.expect_message(ADMIN);
+    assert_eq!(reply.params["text"], "Добавление вопроса #1 в базу знаний отменено");
+    assert_eq!(keyboard(&reply), ["Учебный процесс"]);
+
+    // Выбор категории закончился, сообщение администратора - обычный вопрос
+    api.send_text(ADMIN, "Учебный процесс");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Категория: \"Учебный процесс\"");
+}
+
+fn start_with_group(api: &MockApi) -> Bot {
+    Bot::start_with(api, "polling", &[("BOT_ADMINS", &ADMIN.to_string()), ("BOT_ADMIN_CHAT", &GROUP.to_string())])
+}
+
+/// Сообщение с вопросом в группе администраторов, когда бот запомнит, что оно о вопросе
+fn forwarded(api: &MockApi, bot: &Bot) -> i64 {
+    let forward = api.expect_message(GROUP);
+    let message_id = forward.result["message_id"].as_i64().unwrap();
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    for _ in 0..100 {
+        let remembered: Option<i64> = db.query_row("select question_id from forwarded where message_id = ?1", [message_id], |row| row.get(0))
+                                        .ok();
+        if remembered.is_some() {
+            return message_id;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("бот не запомнил пересланный вопрос");
+}
+
+#[test]
+fn new_question_is_forwarded_to_admin_chat() {
+    let api = MockApi::start();
+    let _bot = start_with_group(&api);
+    save_question(&api, "Где столовая?");
+
+    let forward = api.expect_message(GROUP);
+    assert!(forward.params["text"].as_str().unwrap().starts_with("Новый вопрос #1 от id 42, "), "{forward:?}");
+    assert!(forward.params["text"].as_str().unwrap().ends_with("\nГде столовая?"), "{forward:?}");
+    assert_eq!(inline_buttons(&forward), ["mod:approve:1", "mod:reject:1", "mod:answer:1", "mod:duplicate:1", "mod:promote:1"]);
+
+    api.press_button_as(GROUP, MEMBER, forward.result["message_id"].as_i64().unwrap(), "mod:reject:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Вопрос #1 отклонён");
+}
+
+#[test]
+fn reply_in_admin_chat_answers_asker() {
+    let api = MockApi::start();
+    let bot = start_with_group(&api);
+    save_question(&api, "Где столовая?");
+    let message_id = forwarded(&api, &bot);
+
+    api.send_text_as(GROUP, MEMBER, "На первом этаже", Some(message_id));
+    assert_eq!(messages(&api, 2), [
+        (GROUP, "Ответ на вопрос #1 сохранён и отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где столовая?\":\nНа первом этаже".to_string()),
+    ]);
+}
+
+#[test]
+fn admin_chat_members_only_moderate() {
+    let api = MockApi::start();
+    let bot = start_with_group(&api);
+    save_question(&api, "Где столовая?");
+    let message_id = forwarded(&api, &bot);
+
+    api.send_text_as(GROUP, MEMBER, "/stats", None);
+    assert_eq!(api.expect_message(GROUP).params["text"], "Эта команда доступна только администраторам");
+    api.press_button_as(GROUP, MEMBER, message_id, "mod:promote:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Эта команда доступна только администраторам");
+
+    api.press_button_as(GROUP, MEMBER, message_id, "mod:answer:1");
+    api.expect("answerCallbackQuery");
+    api.expect_message(GROUP);
+    // Ответа ждём только от нажавшего, а переписка остальных боту не адресована
+    api.send_text_as(GROUP, MEMBER + 1, "Кто-нибудь знает?", None);
+    api.send_text_as(GROUP, MEMBER, "На первом этаже", None);
+    assert_eq!(messages(&api, 2), [
+        (GROUP, "Ответ на вопрос #1 сохранён и отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где столовая?\":\nНа первом этаже".to_string()),
+    ]);
+
+    api.send_text_as(GROUP, MEMBER, "Спасибо", None);
+    api.send_text_as(GROUP, MEMBER, "/pending", None);
+    assert_eq!(api.expect_message(GROUP).params["text"], "Эта команда доступна только администраторам");
+}
+
+#[test]
+fn duplicate_answers_with_knowledge_base_answer() {
+    let api = MockApi::start();
+    let bot = start_with_group(&api);
+    save_question(&api, "Когда сдавать практику?");
+    let message_id = forwarded(&api, &bot);
+
+    api.press_button_as(GROUP, MEMBER, message_id, "mod:duplicate:1");
+    api.expect("answerCallbackQuery");
+    let browse = api.expect_message(GROUP);
+    assert_eq!(keyboard(&browse), ["Учебный процесс", "❌ Отмена"]);
+    for category in ["Учебный процесс", "ЦиТХИн", "ИКТ"] {
+        api.send_text_as(GROUP, MEMBER, category, None);
+        api.expect_message(GROUP);
+    }
+    api.send_text_as(GROUP, MEMBER, "Какие сроки сдачи практики?", None);
+    assert_eq!(messages(&api, 2), [
+        (GROUP, "Вопрос #1 отмечен как повтор \"Какие сроки сдачи практики?\", ответ отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"Когда сдавать практику?\":\n1 месяц".to_string()),
+    ]);
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let status: String = db.query_row("select status from questions where id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(status, "duplicate");
+}
+
+#[test]
+fn repeated_questions_are_answered_together() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где находится столовая?");
+    save_question_from(&api, USER + 1, "где находится столовая");
+
+    api.send_text(ADMIN, "/pending");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Вопросов на модерации: 1, страница 1 из 1");
+    let item = api.expect_message(ADMIN);
+    assert!(item.params["text"].as_str().unwrap().ends_with(", спрашивали 2 раза\nГде находится столовая?"), "{item:?}");
+
+    api.send_text(ADMIN, "/answer 1 На первом этаже");
+    assert_eq!(messages(&api, 3), [
+        (ADMIN, "Ответ на вопрос #1 сохранён и отправляется автору и ещё 1 спросившим".to_string()),
+        (USER, "Ответ на ваш вопрос \"Где находится столовая?\":\nНа первом этаже".to_string()),
+        (USER + 1, "Ответ на ваш вопрос \"где находится столовая\":\nНа первом этаже".to_string()),
+    ]);
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let repeat: (String, i64) = db.query_row("select status, cluster_id from questions where id = 2", [], |row| Ok((row.get(0)?, row.get(1)?)))
+                                  .unwrap();
+    assert_eq!(repeat, ("duplicate".to_string(), 1));
+}
+
+#[test]
+fn similar_but_different_questions_are_moderated_separately() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Когда начинается сессия у первого курса?");
+    save_question_from(&api, USER + 1, "Когда начинается сессия у второго курса?");
+
+    api.send_text(ADMIN, "/pending");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Вопросов на модерации: 2, страница 1 из 1");
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let repeats: i64 = db.query_row("select count(*) from questions where cluster_id is not null", [], |row| row.get(0)).unwrap();
+    assert_eq!(repeats, 0);
+}
+
+#[test]
+fn question_similar_to_knowledge_base_is_suggested_to_admins() {
+    let api = MockApi::start();
+    let bot = start_with_group(&api);
+    save_question(&api, "какие сроки сдачи практики");
+
+    // Похожие формулировки бывают и у разных вопросов, поэтому решает модератор
+    let forward = api.expect_message(GROUP);
+    assert!(forward.params["text"].as_str().unwrap().ends_with("\nПохож на вопрос базы знаний \"Какие сроки сдачи практики?\""), "{forward:?}");
+    assert_eq!(inline_buttons(&forward)[0], "mod:similar:1");
+
+    api.press_button_as(GROUP, MEMBER, forward.result["message_id"].as_i64().unwrap(), "mod:similar:1");
+    api.expect("answerCallbackQuery");
+    assert_eq!(messages(&api, 2), [
+        (GROUP, "Вопрос #1 отмечен как повтор \"Какие сроки сдачи практики?\", ответ отправляется автору".to_string()),
+        (USER, "Ответ на ваш вопрос \"какие сроки сдачи практики\":\n1 месяц".to_string()),
+    ]);
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let status: String = db.query_row("select status from questions where id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(status, "duplicate");
+}
+
+/// Пользователь открывает категорию, получает ответ и задаёт непонятый вопрос,
+/// который администратор сохраняет и отвечает
+fn use_bot(api: &MockApi) {
+    api.send_text(USER, "Учебный процесс");
+    api.expect_message(USER);
+    api.send_text(USER, "Какие сроки сдачи практики?");
+    assert_eq!(api.expect_message(USER).params["text"], "1 месяц");
+    api.expect_message(USER);
+    save_question(api, "Где столовая?");
+    api.send_text(ADMIN, "/answer 1 На первом этаже");
+    messages(api, 2);
+}
+
+#[test]
+fn stats_reports_usage() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+    use_bot(&api);
+
+    api.send_text(ADMIN, "/stats");
+    let report = api.expect_message(ADMIN);
+    let report = report.params["text"].as_str().unwrap();
+    assert!(report.starts_with("Вопросов в базе знаний: 4\nСохранённых вопросов пользователей: 1\nЧатов: 2\n\nСтатистика с "), "{report}");
+    for line in ["\nСообщений обработано: 3\n",
+                 "\nПопулярные вопросы:\n  Какие сроки сдачи практики? - 1\n",
+                 "\nПопулярные категории:\n  Учебный процесс - 1\n",
+                 "\nЧастые непонятые запросы:\n  Где столовая? - 1\n",
+                 "\nНа модерации: 0\n",
+                 "\nСреднее время ответа на сохранённый вопрос: "] {
+        assert!(report.contains(line), "{line:?} нет в {report}");
+    }
+    assert!(!report.contains("ответов не было"), "{report}");
+
+    api.send_text(ADMIN, "/stats 30 csv");
+    api.expect_message(ADMIN);
+    let document = api.expect("sendDocument");
+    assert_eq!(document.params["chat_id"], json!(ADMIN));
+    let csv = document.params["document"].as_str().unwrap();
+    assert!(csv.starts_with("section,label,value\n"), "{csv}");
+    assert!(csv.contains("\nmessages,,3\n"), "{csv}");
+    assert!(csv.contains("\ntop_question,\"Какие сроки сдачи практики?\",1\n"), "{csv}");
+
+    api.send_text(ADMIN, "/stats неделю");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Использование: /stats [дней, до 366] [csv]");
+}
+
+#[test]
+fn stats_command_line_report() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    use_bot(&api);
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_mr-deeds"))
+                                       .env("BOT_CONFIG", bot.dir.join("config.toml"))
+                                       .env("BOT_QUESTIONS_DB", bot.questions_db())
+                                       .args(["stats", "--csv"])
+                                       .output()
+                                       .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("\nunmatched,\"Где столовая?\",1\n"), "{stdout}");
+    assert!(stdout.contains("\npending,,0\n"), "{stdout}");
+}
+
+#[test]
+fn stats_command_line_rejects_bad_dates() {
+    let api = MockApi::start();
+    let bot = start(&api);
+
+    let stats = |args: &[&str]| std::process::Command::new(env!("CARGO_BIN_EXE_mr-deeds"))
+                                                  .env("BOT_CONFIG", bot.dir.join("config.toml"))
+                                                  .env("BOT_QUESTIONS_DB", bot.questions_db())
+                                                  .arg("stats")
+                                                  .args(args)
+                                                  .output()
+                                                  .unwrap();
+    for date in ["2024-13-01", "2023-02-29", "2024-1-05", "05.01.2024", "вчера"] {
+        for option in ["--from", "--to"] {
+            let output = stats(&[option, date]);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(!output.status.success(), "{option} {date}");
+            assert!(stderr.contains(&format!("неверная дата {date}, ожидается ГГГГ-ММ-ДД")), "{stderr}");
+        }
+    }
+
+    let output = stats(&["--from", "2024-03-01", "--to", "2024-02-29"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--from 2024-03-01 позже конца периода 2024-02-29"));
+    assert!(stats(&["--from", "2024-02-01", "--to", "2024-02-29"]).status.success());
+}
+
+#[test]
+fn feedback_shows_satisfaction_scores() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    api.send_text(ADMIN, "/feedback");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Ответы пока никто не оценивал.");
+
+    // Первая кнопка - 👍, вторая - 👎, после которой бот предлагает сохранить вопрос
+    for (user, vote) in [(USER, 1), (USER + 1, 0), (USER + 2, 0)] {
+        api.send_text(user, "Какие сроки сдачи практики?");
+        api.expect_message(user);
+        let prompt = api.expect_message(user);
+        api.press_button(user, prompt.result["message_id"].as_i64().unwrap(), &inline_buttons(&prompt)[vote]);
+        api.expect("answerCallbackQuery");
+        if vote == 1 {
+            api.expect_message(user);
+        }
+    }
+
+    api.send_text(ADMIN, "/feedback");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Оценки ответов, сначала худшие:\n67% 👍 (2 👍, 1 👎) Какие сроки сдачи практики?");
+}
+
+#[test]
+fn broadcast_reaches_known_chats() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    api.send_text(USER, "/start");
+    api.expect_message(USER);
+
+    api.send_text(ADMIN, "/broadcast Завтра бот не работает");
+    assert_eq!(messages(&api, 3), [
+        (ADMIN, "Завтра бот не работает".to_string()),
+        (ADMIN, "Рассылка поставлена в очередь, чатов: 2".to_string()),
+        (USER, "Завтра бот не работает".to_string()),
+    ]);
+}
+
+/// Статус доставки ответа, когда бот его запишет
+fn delivery(bot: &Bot) -> (Option<String>, Option<String>) {
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    for _ in 0..100 {
+        let delivery: (Option<String>, Option<String>) = db.query_row(
+            "select delivery, delivery_error from questions where id = 1", [], |row| Ok((row.get(0)?, row.get(1)?))
+        ).unwrap();
+        if delivery.0.as_deref() != Some("pending") {
+            return delivery;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("бот не записал статус доставки");
+}
+
+#[test]
+fn records_answer_delivery() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+
+    api.send_text(ADMIN, "/answer 1 На первом этаже");
+    messages(&api, 2);
+
+    assert_eq!(delivery(&bot), (Some("delivered".to_string()), None));
+}
+
+#[test]
+fn records_failed_answer_delivery() {
+    let api = MockApi::start();
+    let bot = start(&api);
+    save_question(&api, "Где столовая?");
+    api.block_chat(USER);
+
+    api.send_text(ADMIN, "/answer 1 На первом этаже");
+    api.expect_message(ADMIN);
+
+    let (status, error) = delivery(&bot);
+    assert_eq!(status.as_deref(), Some("failed"));
+    assert!(error.is_some_and(|error| error.contains("blocked")));
+}
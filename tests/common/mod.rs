@@ -0,0 +1,380 @@
+//! Общее для интеграционных тестов: локальная замена Bot API и запуск бота
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+/// Сколько ждать вызова от бота, прежде чем считать тест проваленным
+const WAIT: Duration = Duration::from_secs(15);
+
+/// Сколько отправляется сообщение в медленный чат
+const SLOW: Duration = Duration::from_secs(3);
+
+/// Вызов Bot API, сделанный ботом
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub method: String,
+    pub params: Value,
+    /// Что сервер ответил
+    pub result: Value,
+}
+
+#[derive(Default)]
+struct State {
+    /// Обновления, которые бот получит через getUpdates
+    updates: Vec<Value>,
+    calls: Vec<Call>,
+    /// Сколько вызовов каждого метода тест уже проверил
+    seen: HashMap<String, usize>,
+    next_message_id: i64,
+    /// Чаты, заблокировавшие бота: сообщения в них не доставляются
+    blocked: HashSet<i64>,
+    /// Чаты, сообщения в которые отправляются долго
+    slow: HashSet<i64>,
+}
+
+/// HTTP-сервер, изображающий Bot API: отдаёт заранее заданные
+/// обновления и запоминает все вызовы бота
+pub struct MockApi {
+    pub url: String,
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl MockApi {
+    pub fn start() -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let state = Arc::new((Mutex::new(State { next_message_id: 1000, ..State::default() }), Condvar::new()));
+
+        let worker_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                // getUpdates висит, пока нет обновлений, поэтому каждый запрос в своём потоке
+                let state = Arc::clone(&worker_state);
+                thread::spawn(move || handle(request, &state));
+            }
+        });
+
+        MockApi { url, state }
+    }
+
+    /// Добавляет обновление (`{"message": ...}`, `{"callback_query": ...}`)
+    pub fn push_update(&self, mut update: Value) {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        update["update_id"] = json!(state.updates.len() + 1);
+        state.updates.push(update);
+        changed.notify_all();
+    }
+
+    pub fn send_text(&self, chat_id: i64, text: &str) {
+        self.send_text_as(chat_id, chat_id, text, None);
+    }
+
+    /// Сообщение от пользователя `from` (в группе он не совпадает с чатом),
+    /// возможно, ответ на сообщение бота `reply_to`
+    pub fn send_text_as(&self, chat_id: i64, from: i64, text: &str, reply_to: Option<i64>) {
+        let mut message = message(chat_id, from, text);
+        if let Some(replied) = reply_to {
+            message["reply_to_message"] = json!({ "message_id": replied, "date": 0, "chat": chat(chat_id) });
+        }
+        if text.starts_with('/') {
+            let length = text.split_whitespace().next().unwrap_or(text).encode_utf16().count();
+            message["entities"] = json!([{ "type": "bot_command", "offset": 0, "length": length }]);
+        }
+        self.push_update(json!({ "message": message }));
+    }
+
+    pub fn send_sticker(&self, chat_id: i64) {
+        let mut message = message(chat_id, chat_id, "");
+        message.as_object_mut().unwrap().remove("text");
+        message["sticker"] = json!({
+            "file_id": "sticker", "file_unique_id": "sticker", "type": "regular",
+            "width": 512, "height": 512, "is_animated": false, "is_video": false,
+        });
+        self.push_update(json!({ "message": message }));
+    }
+
+    /// Нажатие кнопки под сообщением бота `message_id`
+    pub fn press_button(&self, chat_id: i64, message_id: i64, data: &str) {
+        self.press_button_as(chat_id, chat_id, message_id, data);
+    }
+
+    pub fn press_button_as(&self, chat_id: i64, from: i64, message_id: i64, data: &str) {
+        self.push_update(json!({ "callback_query": {
+            "id": format!("press-{chat_id}-{message_id}-{data}"),
+            "from": user(from),
+            "message": {
+                "message_id": message_id,
+                "date": 0,
+                "chat": chat(chat_id),
+            },
+            "chat_instance": "test",
+            "data": data,
+        }}));
+    }
+
+    /// Ждёт следующего ещё не проверенного вызова метода
+    pub fn expect(&self, method: &str) -> Call {
+        let (state, changed) = &*self.state;
+        let deadline = Instant::now() + WAIT;
+        let mut state = state.lock().unwrap();
+
+        loop {
+            let seen = state.seen.get(method).copied().unwrap_or(0);
+            if let Some(call) = state.calls.iter().filter(|call| call.method == method).nth(seen).cloned() {
+                state.seen.insert(method.to_string(), seen + 1);
+                return call;
+            }
+
+            let now = Instant::now();
+            assert!(now < deadline, "бот не вызвал {method}; вызовы: {:#?}", state.calls);
+            state = changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Ждёт следующего сообщения бота в чат
+    pub fn expect_message(&self, chat_id: i64) -> Call {
+        let call = self.expect("sendMessage");
+        assert_eq!(call.params["chat_id"], json!(chat_id), "сообщение ушло не в тот чат: {call:#?}");
+        call
+    }
+
+    /// Пользователь чата заблокировал бота
+    pub fn block_chat(&self, chat_id: i64) {
+        self.state.0.lock().unwrap().blocked.insert(chat_id);
+    }
+
+    /// Сообщения в чат будут отправляться по несколько секунд
+    pub fn slow_chat(&self, chat_id: i64) {
+        self.state.0.lock().unwrap().slow.insert(chat_id);
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.0.lock().unwrap().calls.clone()
+    }
+}
+
+fn user(id: i64) -> Value {
+    json!({ "id": id, "is_bot": false, "first_name": "Тест" })
+}
+
+/// Отрицательные id у групп
+fn chat(id: i64) -> Value {
+    json!({ "id": id, "type": if id < 0 { "group" } else { "private" } })
+}
+
+/// Обновление с текстовым сообщением, как его присылает Telegram
+pub fn text_update(update_id: i64, chat_id: i64, text: &str) -> Value {
+    json!({ "update_id": update_id, "message": message(chat_id, chat_id, text) })
+}
+
+fn message(chat_id: i64, from: i64, text: &str) -> Value {
+    json!({
+        "message_id": 1,
+        "date": 0,
+        "chat": chat(chat_id),
+        "from": user(from),
+        "text": text,
+    })
+}
+
+fn handle(mut request: tiny_http::Request, state: &(Mutex<State>, Condvar)) {
+    let method = request.url().rsplit('/').next().unwrap_or_default().to_string();
+    let mut body = String::new();
+    let _ = request.as_reader().read_to_string(&mut body);
+    // Файлы бот загружает формой, остальные вызовы - JSON
+    let boundary = request.headers()
+                          .iter()
+                          .find(|header| header.field.equiv("Content-Type"))
+                          .and_then(|header| header.value.as_str().split_once("boundary=").map(|(_, boundary)| boundary.to_string()));
+    let params: Value = match boundary {
+        Some(boundary) => form_fields(&body, &boundary),
+        None => serde_json::from_str(&body).unwrap_or(json!({})),
+    };
+
+    let slow = method == "sendMessage"
+               && params["chat_id"].as_i64().is_some_and(|chat| state.0.lock().unwrap().slow.contains(&chat));
+    if slow {
+        thread::sleep(SLOW);
+    }
+
+    let blocked = method == "sendMessage"
+                  && params["chat_id"].as_i64().is_some_and(|chat| state.0.lock().unwrap().blocked.contains(&chat));
+    if blocked {
+        let response = json!({ "ok": false, "error_code": 403, "description": "Forbidden: bot was blocked by the user" });
+        let _ = request.respond(Response::from_string(response.to_string()).with_status_code(403));
+        return;
+    }
+
+    let result = match method.as_str() {
+        "getUpdates" => Some(get_updates(&params, state)),
+        "sendMessage" | "sendDocument" => {
+            let mut state = state.0.lock().unwrap();
+            state.next_message_id += 1;
+            Some(json!({
+                "message_id": state.next_message_id,
+                "date": 0,
+                "chat": chat(params["chat_id"].as_i64().unwrap_or_default()),
+                "text": params["text"],
+            }))
+        }
+        "answerCallbackQuery" | "editMessageReplyMarkup" | "setMyCommands" | "deleteWebhook" | "setWebhook"
+            => Some(json!(true)),
+        _ => None,
+    };
+
+    let response = match result {
+        Some(result) => {
+            if method != "getUpdates" {
+                let (state, changed) = state;
+                let call = Call { method, params, result: result.clone() };
+                state.lock().unwrap().calls.push(call);
+                changed.notify_all();
+            }
+            json!({ "ok": true, "result": result })
+        }
+        None => json!({ "ok": false, "error_code": 404, "description": "Not Found" }),
+    };
+
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let _ = request.respond(Response::from_string(response.to_string()).with_header(header));
+}
+
+/// Поля формы `multipart/form-data` как объект: у файла - его содержимое
+fn form_fields(body: &str, boundary: &str) -> Value {
+    let mut fields = serde_json::Map::new();
+    for part in body.split(&format!("--{boundary}")) {
+        let Some((headers, content)) = part.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let name = headers.split("name=\"").nth(1).and_then(|rest| rest.split('"').next());
+        if let Some(name) = name {
+            fields.insert(name.to_string(), json!(content.strip_suffix("\r\n").unwrap_or(content)));
+        }
+    }
+    // Числа форма передаёт строками
+    if let Some(chat_id) = fields.get("chat_id").and_then(|chat_id| chat_id.as_str()?.parse::<i64>().ok()) {
+        fields.insert("chat_id".to_string(), json!(chat_id));
+    }
+
+    Value::Object(fields)
+}
+
+/// Обновления начиная с `offset`; если их нет, ждём до `timeout` секунд
+fn get_updates(params: &Value, (state, changed): &(Mutex<State>, Condvar)) -> Value {
+    let offset = params["offset"].as_u64().unwrap_or(0) as usize;
+    let timeout = Duration::from_secs(params["timeout"].as_u64().unwrap_or(0));
+    let deadline = Instant::now() + timeout;
+
+    let mut state = state.lock().unwrap();
+    loop {
+        let pending: Vec<Value> = state.updates.iter().skip(offset.saturating_sub(1)).cloned().collect();
+        let now = Instant::now();
+        if !pending.is_empty() || now >= deadline {
+            return json!(pending);
+        }
+        state = changed.wait_timeout(state, deadline - now).unwrap().0;
+    }
+}
+
+/// Запущенный бот; останавливается, когда тест заканчивается
+pub struct Bot {
+    child: Child,
+    pub dir: PathBuf,
+}
+
+impl Bot {
+    /// Запускает бота в режиме `mode` с копией базы знаний из tests/data/qna.json
+    pub fn start(api: &MockApi, mode: &str) -> Self {
+        Self::start_with(api, mode, &[])
+    }
+
+    /// Как `start`, с дополнительными переменными окружения
+    pub fn start_with(api: &MockApi, mode: &str, env: &[(&str, &str)]) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "mr-deeds-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::SeqCst)));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Бот может дописывать базу знаний, поэтому работает с копией
+        std::fs::copy(data_file("qna.json"), dir.join("qna.json")).unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_mr-deeds"))
+                            .env("BOT_CONFIG", dir.join("config.toml"))
+                            .env("BOT_TOKEN", "test")
+                            .env("BOT_API_URL", &api.url)
+                            .env("BOT_MODE", mode)
+                            .env("BOT_POLL_TIMEOUT", "1")
+                            .env("BOT_KNOWLEDGE_BASE", dir.join("qna.json"))
+                            .env("BOT_SNAPSHOT", dir.join("db.snapshot"))
+                            .env("BOT_QUESTIONS_DB", dir.join("questions.db"))
+                            .envs(env.iter().copied())
+                            .stdout(Stdio::null())
+                            .stderr(Stdio::null())
+                            .spawn()
+                            .unwrap();
+
+        Bot { child, dir }
+    }
+
+    pub fn questions_db(&self) -> PathBuf {
+        self.dir.join("questions.db")
+    }
+
+    pub fn knowledge_base(&self) -> PathBuf {
+        self.dir.join("qna.json")
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)
+}
+
+/// Следующие `count` сообщений бота как пары (чат, текст), по порядку чатов:
+/// сообщения в разные чаты уходят в произвольном порядке
+pub fn messages(api: &MockApi, count: usize) -> Vec<(i64, String)> {
+    let mut texts: Vec<(i64, String)> = (0..count).map(|_| api.expect("sendMessage"))
+                                                  .map(|call| (call.params["chat_id"].as_i64().unwrap(),
+                                                               call.params["text"].as_str().unwrap().to_string()))
+                                                  .collect();
+    texts.sort();
+    texts
+}
+
+/// Тексты кнопок обычной клавиатуры из параметров sendMessage
+pub fn keyboard(call: &Call) -> Vec<String> {
+    call.params["reply_markup"]["keyboard"]
+        .as_array()
+        .map(|rows| rows.iter()
+                        .flat_map(|row| row.as_array().cloned().unwrap_or_default())
+                        .filter_map(|button| button["text"].as_str().map(str::to_owned))
+                        .collect())
+        .unwrap_or_default()
+}
+
+/// Данные кнопок под сообщением из параметров sendMessage
+pub fn inline_buttons(call: &Call) -> Vec<String> {
+    call.params["reply_markup"]["inline_keyboard"]
+        .as_array()
+        .map(|rows| rows.iter()
+                        .flat_map(|row| row.as_array().cloned().unwrap_or_default())
+                        .filter_map(|button| button["callback_data"].as_str().map(str::to_owned))
+                        .collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,76 @@
+//! HTTP-сервер метрик Prometheus и проверки живости
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{Bot, MockApi};
+
+const CHAT: i64 = 42;
+
+/// Свободный порт на localhost для сервера метрик
+fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// GET-запрос к серверу метрик: код ответа и тело. Сервер поднимается
+/// вместе с ботом, поэтому подключаемся с повторами
+fn get(address: &str, path: &str) -> (u16, String) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut stream = loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => break stream,
+            Err(error) if Instant::now() > deadline => panic!("сервер метрик не запустился: {error}"),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+
+    write!(stream, "GET {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    (status, body.to_string())
+}
+
+/// Метрики, как только в них появятся все строки `expected`
+fn wait_metrics(address: &str, expected: &[&str]) -> String {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let (status, body) = get(address, "/metrics");
+        assert_eq!(status, 200);
+        if expected.iter().all(|line| body.lines().any(|metric| metric == *line)) {
+            return body;
+        }
+        assert!(Instant::now() < deadline, "в метриках нет {expected:?}:\n{body}");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn exposes_metrics_and_health() {
+    let api = MockApi::start();
+    let address = free_address();
+    let _bot = Bot::start_with(&api, "polling", &[("BOT_METRICS_LISTEN", &address)]);
+
+    api.send_text(CHAT, "Где столовая?");
+    api.expect_message(CHAT);
+
+    let body = wait_metrics(&address, &[
+        "bot_updates_received_total 1",
+        "bot_queries_total{result=\"unknown\"} 1",
+        "bot_queries_total{result=\"answered\"} 0",
+        "bot_unknown_query_ratio 1",
+        "bot_replies_sent_total{method=\"sendMessage\"} 1",
+    ]);
+    assert!(body.contains("# TYPE bot_get_updates_duration_seconds histogram"));
+    assert!(body.lines().any(|line| line.starts_with("bot_knowledge_nodes ") && line != "bot_knowledge_nodes 0"));
+
+    assert_eq!(get(&address, "/healthz"), (200, "ok".to_string()));
+    assert_eq!(get(&address, "/nothing").0, 404);
+}
@@ -0,0 +1,147 @@
+//! Разговоры пользователей с операторами через локальную замену Bot API
+
+mod common;
+
+use common::{inline_buttons, keyboard, messages, Bot, Call, MockApi};
+
+const USER: i64 = 42;
+/// Группа операторов и оператор в ней
+const OPERATORS: i64 = -200;
+const OPERATOR: i64 = 200;
+
+fn start(api: &MockApi) -> Bot {
+    Bot::start_with(api, "polling", &[("BOT_OPERATOR_CHAT", &OPERATORS.to_string())])
+}
+
+/// Два следующих сообщения бота: пользователю и в группу операторов, в любом порядке
+fn user_and_operators(api: &MockApi) -> (Call, Call) {
+    let (first, second) = (api.expect("sendMessage"), api.expect("sendMessage"));
+    match first.params["chat_id"].as_i64() {
+        Some(USER) => (first, second),
+        _ => (second, first),
+    }
+}
+
+/// Номер сообщения бота в группе операторов, когда бот запомнит, к какому обращению оно относится
+fn relayed(bot: &Bot, call: &Call) -> i64 {
+    assert_eq!(call.params["chat_id"].as_i64(), Some(OPERATORS), "{call:?}");
+    let message_id = call.result["message_id"].as_i64().unwrap();
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    for _ in 0..100 {
+        let remembered: Option<i64> = db.query_row("select ticket_id from relayed where message_id = ?1", [message_id], |row| row.get(0))
+                                        .ok();
+        if remembered.is_some() {
+            return message_id;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("бот не запомнил сообщение обращения");
+}
+
+#[test]
+fn registers_operator_command() {
+    let api = MockApi::start();
+    let _bot = start(&api);
+
+    let call = api.expect("setMyCommands");
+    let commands: Vec<&str> = call.params["commands"]
+                                  .as_array()
+                                  .unwrap()
+                                  .iter()
+                                  .filter_map(|command| command["command"].as_str())
+                                  .collect();
+    assert_eq!(commands, ["start", "reset", "help", "operator"]);
+}
+
+#[test]
+fn relays_conversation_until_user_closes_it() {
+    let api = MockApi::start();
+    let bot = start(&api);
+
+    api.send_text(USER, "Где столовая?");
+    let reply = api.expect_message(USER);
+    assert_eq!(inline_buttons(&reply), ["save", "nosave", "operator"]);
+    api.press_button(USER, reply.result["message_id"].as_i64().unwrap(), "operator");
+    api.expect("answerCallbackQuery");
+    api.expect("editMessageReplyMarkup");
+
+    let (connected, header) = user_and_operators(&api);
+    assert_eq!(connected.params["text"], "Вы подключены к оператору. Пишите, оператор ответит здесь.");
+    assert_eq!(keyboard(&connected), ["Завершить разговор"]);
+    assert_eq!(header.params["text"], "Обращение #1 от id 42\nГде столовая?");
+    assert_eq!(inline_buttons(&header), ["ticket:close:1"]);
+
+    api.send_text(USER, "Я на втором этаже");
+    let relay = api.expect_message(OPERATORS);
+    assert_eq!(relay.params["text"], "#1 id 42: Я на втором этаже");
+    let message_id = relayed(&bot, &relay);
+
+    api.send_text_as(OPERATORS, OPERATOR, "Спуститесь на первый", Some(message_id));
+    assert_eq!(api.expect_message(USER).params["text"], "Оператор: Спуститесь на первый");
+
+    api.send_text(USER, "Завершить разговор");
+    assert_eq!(messages(&api, 2), [
+        (OPERATORS, "Обращение #1 закрыто пользователем".to_string()),
+        (USER, "Разговор с оператором завершён.".to_string()),
+    ]);
+
+    // Дальше бот отвечает сам
+    api.send_text(USER, "Q");
+    assert_eq!(api.expect_message(USER).params["text"], "A");
+}
+
+#[test]
+fn operator_closes_ticket() {
+    let api = MockApi::start();
+    let bot = start(&api);
+
+    api.send_text(USER, "/operator");
+    let (_, header) = user_and_operators(&api);
+    assert_eq!(header.params["text"], "Обращение #1 от id 42");
+    let message_id = relayed(&bot, &header);
+
+    api.send_text_as(OPERATORS, OPERATOR, "/close", Some(message_id));
+    assert_eq!(messages(&api, 2), [
+        (OPERATORS, "Обращение #1 закрыто оператором".to_string()),
+        (USER, "Разговор с оператором завершён.".to_string()),
+    ]);
+
+    api.send_text_as(OPERATORS, OPERATOR, "Вы тут?", Some(message_id));
+    assert_eq!(api.expect_message(OPERATORS).params["text"], "Обращение #1 уже закрыто");
+}
+
+#[test]
+fn operator_texts_follow_bot_language() {
+    let api = MockApi::start();
+    let bot = Bot::start_with(&api, "polling", &[("BOT_OPERATOR_CHAT", &OPERATORS.to_string()), ("BOT_LANGUAGE", "en")]);
+
+    api.send_text(USER, "/operator");
+    let (_, header) = user_and_operators(&api);
+    assert_eq!(header.params["text"], "Ticket #1 from id 42");
+    let close: Vec<&str> = header.params["reply_markup"]["inline_keyboard"][0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|button| button["text"].as_str())
+        .collect();
+    assert_eq!(close, ["Close ticket"]);
+    let message_id = relayed(&bot, &header);
+
+    api.press_button_as(OPERATORS, OPERATOR, message_id, "ticket:close:1");
+    api.expect("answerCallbackQuery");
+    api.expect("editMessageReplyMarkup");
+    assert_eq!(messages(&api, 2)[0], (OPERATORS, "Ticket #1 closed by an operator".to_string()));
+
+    api.press_button_as(OPERATORS, OPERATOR, message_id, "ticket:close:1");
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Ticket #1 is already closed");
+}
+
+#[test]
+fn operator_is_unavailable_without_operator_chat() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "polling");
+
+    api.send_text(USER, "/operator");
+    assert_eq!(api.expect_message(USER).params["text"], "Сейчас операторы недоступны.");
+}
@@ -0,0 +1,39 @@
+//! Сценарии разговоров из tests/scenarios на тестовой базе знаний
+
+mod common;
+
+use std::process::Command;
+
+use common::data_file;
+
+fn run_scenarios(files: &[std::path::PathBuf]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_mr-deeds"))
+            .env("BOT_CONFIG", data_file("missing-config.toml"))
+            .arg("scenarios")
+            .arg(data_file("qna.json"))
+            .args(files)
+            .output()
+            .unwrap()
+}
+
+#[test]
+fn scenarios_pass() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let files: Vec<_> = std::fs::read_dir(dir).unwrap()
+                                              .map(|entry| entry.unwrap().path())
+                                              .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                                              .collect();
+    assert!(!files.is_empty());
+
+    let output = run_scenarios(&files);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn reports_mismatches() {
+    let output = run_scenarios(&[data_file("broken_scenario.toml")]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(!output.status.success());
+    assert!(stdout.contains("ожидался ответ \"2 месяца\", получено [\"1 месяц\", \"Ответ помог?\"]"), "{stdout}");
+}
@@ -0,0 +1,209 @@
+//! Полные разговоры с ботом через локальную замену Bot API
+
+mod common;
+
+use common::{inline_buttons, keyboard, Bot, MockApi};
+use serde_json::json;
+
+const CHAT: i64 = 42;
+
+#[test]
+fn registers_commands_on_start() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "polling");
+
+    let call = api.expect("setMyCommands");
+    let commands: Vec<&str> = call.params["commands"]
+                                  .as_array()
+                                  .unwrap()
+                                  .iter()
+                                  .filter_map(|command| command["command"].as_str())
+                                  .collect();
+    assert_eq!(commands, ["start", "reset", "help"]);
+}
+
+#[test]
+fn answers_question_from_knowledge_base() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "polling");
+
+    api.send_text(CHAT, "/start");
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "Привет! Я бот!");
+    assert_eq!(keyboard(&reply), ["Учебный процесс"]);
+
+    api.send_text(CHAT, "Учебный процесс");
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "Категория: \"Учебный процесс\"");
+    assert_eq!(keyboard(&reply), ["ЦиТХИн", "МСЭН"]);
+
+    api.send_text(CHAT, "Какие сроки сдачи практики?");
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "1 месяц");
+    assert_eq!(keyboard(&reply), ["Учебный процесс"]);
+}
+
+#[test]
+fn saves_unknown_question_on_request() {
+    let api = MockApi::start();
+    let bot = Bot::start(&api, "polling");
+
+    api.send_text(CHAT, "Где столовая?");
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "Прости, я не знаю ответа на твой вопрос...");
+    assert_eq!(inline_buttons(&reply), ["save", "nosave"]);
+
+    let message_id = reply.result["message_id"].as_i64().unwrap();
+    api.press_button(CHAT, message_id, "save");
+
+    let answer = api.expect("answerCallbackQuery");
+    assert_eq!(answer.params["text"], "Я сохранил твой выбор, спасибо!");
+    let edit = api.expect("editMessageReplyMarkup");
+    assert_eq!(edit.params["message_id"], json!(message_id));
+    assert_eq!(edit.params["chat_id"], json!(CHAT));
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let saved: (u64, String) = db.query_row("select user_id, question from questions", [], |row| Ok((row.get(0)?, row.get(1)?)))
+                                 .unwrap();
+    assert_eq!(saved, (CHAT as u64, "Где столовая?".to_string()));
+}
+
+#[test]
+fn records_analytics_events() {
+    let api = MockApi::start();
+    let bot = Bot::start(&api, "polling");
+
+    for text in ["/start", "Учебный процесс", "Какие сроки сдачи практики?"] {
+        api.send_text(CHAT, text);
+        api.expect_message(CHAT);
+    }
+    api.send_text(CHAT, "Где столовая?");
+    let reply = api.expect_message(CHAT);
+    api.press_button(CHAT, reply.result["message_id"].as_i64().unwrap(), "save");
+    api.expect("answerCallbackQuery");
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let mut stmt = db.prepare("select kind, label from events order by id").unwrap();
+    let events: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                                            .unwrap()
+                                            .collect::<Result<_, _>>()
+                                            .unwrap();
+    assert_eq!(events, [
+        ("command".to_string(), "/start".to_string()),
+        ("category".to_string(), "Учебный процесс".to_string()),
+        ("answer".to_string(), "Какие сроки сдачи практики?".to_string()),
+        ("unknown".to_string(), "Где столовая?".to_string()),
+        ("save".to_string(), "Где столовая?".to_string()),
+    ]);
+}
+
+#[test]
+fn unhelpful_answer_offers_to_save_question() {
+    let api = MockApi::start();
+    let bot = Bot::start(&api, "polling");
+
+    api.send_text(CHAT, "Какие сроки сдачи практики?");
+    assert_eq!(api.expect_message(CHAT).params["text"], "1 месяц");
+    let prompt = api.expect_message(CHAT);
+    assert_eq!(prompt.params["text"], "Ответ помог?");
+    let buttons = inline_buttons(&prompt);
+    assert!(buttons[0].starts_with("feedback:up:") && buttons[1].starts_with("feedback:down:"), "{buttons:?}");
+
+    api.press_button(CHAT, prompt.result["message_id"].as_i64().unwrap(), &buttons[1]);
+    assert_eq!(api.expect("answerCallbackQuery").params["text"], "Спасибо за оценку!");
+    api.expect("editMessageReplyMarkup");
+    let offer = api.expect_message(CHAT);
+    assert_eq!(offer.params["text"], "Жаль! Сохранить вопрос, чтобы на него ответил человек?");
+    assert_eq!(inline_buttons(&offer), ["save", "nosave"]);
+
+    api.press_button(CHAT, offer.result["message_id"].as_i64().unwrap(), "save");
+    api.expect("answerCallbackQuery");
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let vote: bool = db.query_row("select helpful from feedback where question = 'Какие сроки сдачи практики?'", [], |row| row.get(0))
+                       .unwrap();
+    assert!(!vote);
+    let saved: (String, String, bool) = db.query_row("select question, status, unhelpful from questions", [],
+                                                     |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                                          .unwrap();
+    assert_eq!(saved, ("Какие сроки сдачи практики?".to_string(), "new".to_string(), true));
+}
+
+/// Пользователь ставит 👎 ответу на вопрос базы знаний и сохраняет вопрос
+fn complain(api: &MockApi, chat_id: i64, question: &str) {
+    api.send_text(chat_id, question);
+    api.expect_message(chat_id);
+    let prompt = api.expect_message(chat_id);
+    api.press_button(chat_id, prompt.result["message_id"].as_i64().unwrap(), &inline_buttons(&prompt)[1]);
+    api.expect("answerCallbackQuery");
+    api.expect("editMessageReplyMarkup");
+    let offer = api.expect_message(chat_id);
+    api.press_button(chat_id, offer.result["message_id"].as_i64().unwrap(), "save");
+    api.expect("answerCallbackQuery");
+    api.expect("editMessageReplyMarkup");
+}
+
+#[test]
+fn complaints_about_different_answers_are_not_merged() {
+    const ADMIN: i64 = 7;
+    let api = MockApi::start();
+    let bot = Bot::start_with(&api, "polling", &[("BOT_ADMINS", &ADMIN.to_string())]);
+    complain(&api, CHAT, "Q");
+    complain(&api, CHAT + 1, "Q2");
+
+    let db = rusqlite::Connection::open(bot.questions_db()).unwrap();
+    let mut stmt = db.prepare("select question, status from questions order by id").unwrap();
+    let saved: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().map(Result::unwrap).collect();
+    assert_eq!(saved, [("Q".to_string(), "new".to_string()), ("Q2".to_string(), "new".to_string())]);
+
+    // Модераторы видят, на что жалуется пользователь
+    api.send_text(ADMIN, "/pending");
+    assert_eq!(api.expect_message(ADMIN).params["text"], "Вопросов на модерации: 2, страница 1 из 1");
+    let first = api.expect_message(ADMIN);
+    assert!(first.params["text"].as_str().unwrap().ends_with("\nНе помог ответ на вопрос \"Q\""), "{first:?}");
+}
+
+#[test]
+fn rejects_non_text_messages() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "polling");
+
+    api.send_sticker(CHAT);
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "Извини, я понимаю только вопросы текстом!");
+}
+
+#[test]
+fn async_mode_keeps_order_within_chat() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "async");
+
+    api.send_text(CHAT, "/start");
+    api.send_text(CHAT, "Q");
+    api.send_text(CHAT + 1, "/help");
+
+    let mut texts = vec![];
+    for _ in 0..3 {
+        let call = api.expect("sendMessage");
+        texts.push((call.params["chat_id"].as_i64().unwrap(), call.params["text"].as_str().unwrap().to_string()));
+    }
+    let chat: Vec<&str> = texts.iter().filter(|(chat, _)| *chat == CHAT).map(|(_, text)| text.as_str()).collect();
+    assert_eq!(chat, ["Привет! Я бот!", "A"]);
+    assert!(texts.contains(&(CHAT + 1, "Выбери вопрос или напиши свой".to_string())));
+}
+
+#[test]
+fn async_mode_slow_chat_does_not_delay_others() {
+    let api = MockApi::start();
+    let _bot = Bot::start(&api, "async");
+    api.slow_chat(CHAT);
+
+    api.send_text(CHAT, "/start");
+    // Пусть отправка в медленный чат успеет начаться
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    api.send_text(CHAT + 1, "/help");
+
+    // Вызов записывается, когда сервер ответил
+    assert_eq!(api.expect("sendMessage").params["chat_id"], CHAT + 1);
+    assert_eq!(api.expect("sendMessage").params["chat_id"], CHAT);
+}
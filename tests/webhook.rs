@@ -0,0 +1,57 @@
+//! Режим вебхука: обновления приходят POST-запросами на встроенный сервер
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{text_update, Bot, MockApi};
+
+const CHAT: i64 = 42;
+const SECRET: &str = "s3cret";
+
+/// POST-запрос к вебхуку с заголовком секрета `secret`; возвращает код ответа.
+/// Сервер поднимается вместе с ботом, поэтому подключаемся с повторами
+fn post(address: &str, secret: Option<&str>, body: &str) -> u16 {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut stream = loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => break stream,
+            Err(error) if Instant::now() > deadline => panic!("вебхук не запустился: {error}"),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+
+    let secret = secret.map(|secret| format!("X-Telegram-Bot-Api-Secret-Token: {secret}\r\n")).unwrap_or_default();
+    write!(stream, "POST / HTTP/1.1\r\nHost: {address}\r\nContent-Type: application/json\r\n{secret}\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response.split(' ').nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn accepts_only_updates_with_secret() {
+    let api = MockApi::start();
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let _bot = Bot::start_with(&api, "webhook", &[
+        ("BOT_WEBHOOK_URL", "https://example.com/hook"),
+        ("BOT_WEBHOOK_LISTEN", &address),
+        ("BOT_WEBHOOK_SECRET", SECRET),
+    ]);
+
+    let call = api.expect("setWebhook");
+    assert_eq!(call.params["secret_token"], SECRET);
+
+    assert_eq!(post(&address, None, &text_update(1, CHAT, "/start").to_string()), 401);
+    assert_eq!(post(&address, Some("wrong"), &text_update(2, CHAT, "/start").to_string()), 401);
+    assert_eq!(post(&address, Some(SECRET), &text_update(3, CHAT, "Где столовая?").to_string()), 200);
+
+    // Отвечает только на принятое обновление
+    let reply = api.expect_message(CHAT);
+    assert_eq!(reply.params["text"], "Прости, я не знаю ответа на твой вопрос...");
+    assert_eq!(api.calls().iter().filter(|call| call.method == "sendMessage").count(), 1);
+}